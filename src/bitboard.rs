@@ -0,0 +1,436 @@
+/* bitboard.rs -- board packed in u64 with row lookup tables.
+Copyright (C) 2021 fuggy
+
+This file is part of game-2048-engine.
+
+game-2048-engine is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+game-2048-engine is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with game-2048-engine.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::board::{self, Board, BOARD_SIZE};
+use crate::cache::slide_cache::SlideCache;
+use crate::direction::Direction;
+use crate::matrix;
+use crate::symmetry::Symmetry;
+use std::sync::OnceLock;
+
+/// Board packed in u64 as matrix::to_u64, 4 bits per cell,
+/// first row in the high 16 bits, first cell of a row in the high 4 bits
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub struct BitBoard(pub u64);
+
+/// Xor deltas of moved rows and columns, score of moved rows
+struct BitTables {
+    row_left: Vec<u64>,
+    row_right: Vec<u64>,
+    col_up: Vec<u64>,
+    col_down: Vec<u64>,
+    score_left: Vec<u32>,
+    score_right: Vec<u32>,
+}
+
+static BIT_TABLES: OnceLock<BitTables> = OnceLock::new();
+
+const ROW_MASK: u64 = 0xFFFF;
+/// Low bit of every cell
+const CELL_LOW_BITS: u64 = 0x1111_1111_1111_1111;
+/// Cells having the left neighbour in the next 4 bits
+const HORIZONTAL_PAIRS: u64 = 0x0111_0111_0111_0111;
+/// Cells having the upper neighbour in the next 16 bits
+const VERTICAL_PAIRS: u64 = 0x0000_1111_1111_1111;
+
+/// Tables are built from slide cache by the first move of any thread
+fn tables() -> &'static BitTables {
+    BIT_TABLES.get_or_init(|| BitTables::from_cache(board::slide_cache()))
+}
+
+impl BitTables {
+    /// Every row slided left is in the slide cache, right is reversed left
+    fn from_cache(cache: &SlideCache) -> Self {
+        let size = cache.table.len();
+        let mut tables = BitTables {
+            row_left: vec![0; size],
+            row_right: vec![0; size],
+            col_up: vec![0; size],
+            col_down: vec![0; size],
+            score_left: vec![0; size],
+            score_right: vec![0; size],
+        };
+
+        for row in 0..size {
+            let item = cache.table[row];
+            if item.line == 0 {
+                //not moved
+                continue;
+            }
+            let row = row as u16;
+            let delta = row ^ item.line;
+            let score = if item.score == 0 {
+                0
+            } else {
+                item.score as u32 + 1
+            };
+            let rev = reverse_row(row) as usize;
+            let rev_delta = reverse_row(delta);
+
+            tables.row_left[row as usize] = delta as u64;
+            tables.col_up[row as usize] = unpack_column(delta);
+            tables.score_left[row as usize] = score;
+            tables.row_right[rev] = rev_delta as u64;
+            tables.col_down[rev] = unpack_column(rev_delta);
+            tables.score_right[rev] = score;
+        }
+        tables
+    }
+}
+
+/// Reverse order of cells in a row
+fn reverse_row(row: u16) -> u16 {
+    (row >> 12) | ((row >> 4) & 0x00F0) | ((row << 4) & 0x0F00) | (row << 12)
+}
+
+/// Place row cells in the first column, first cell on top
+fn unpack_column(row: u16) -> u64 {
+    let row = row as u64;
+    ((row & 0xF000) << 48)
+        | ((row & 0x0F00) << 36)
+        | ((row & 0x00F0) << 24)
+        | ((row & 0x000F) << 12)
+}
+
+/// Cells equal to zero get the low bit set, others are cleared
+fn zero_cells(x: u64) -> u64 {
+    let x = x | (x >> 2);
+    let x = x | (x >> 1);
+    !x & CELL_LOW_BITS
+}
+
+/// Shift of the cell in the packed board
+fn cell_shift(j: usize, i: usize) -> u32 {
+    (60 - 16 * j - 4 * i) as u32
+}
+
+impl From<&Board> for BitBoard {
+    fn from(board: &Board) -> Self {
+        board
+            .packed()
+            .unwrap_or_else(|| BitBoard(matrix::to_u64(&board.cells())))
+    }
+}
+
+impl BitBoard {
+    /// Rows become columns, the cell order of transposed nibbles is symmetric
+    pub fn transpose(self) -> Self {
+        let x = self.0;
+        let a1 = x & 0xF0F0_0F0F_F0F0_0F0F;
+        let a2 = x & 0x0000_F0F0_0000_F0F0;
+        let a3 = x & 0x0F0F_0000_0F0F_0000;
+        let a = a1 | (a2 << 12) | (a3 >> 12);
+        let b1 = a & 0xFF00_FF00_00FF_00FF;
+        let b2 = a & 0x00FF_00FF_0000_0000;
+        let b3 = a & 0x0000_0000_FF00_FF00;
+        BitBoard(b1 | (b2 >> 24) | (b3 << 24))
+    }
+
+    /// Rows in reverse order
+    pub fn mirror_h(self) -> Self {
+        let x = self.0.rotate_left(32);
+        BitBoard(((x & 0xFFFF_0000_FFFF_0000) >> 16) | ((x & 0x0000_FFFF_0000_FFFF) << 16))
+    }
+
+    /// Cells of every row in reverse order
+    pub fn mirror_v(self) -> Self {
+        let x = self.0;
+        let x = ((x & 0xFF00_FF00_FF00_FF00) >> 8) | ((x & 0x00FF_00FF_00FF_00FF) << 8);
+        BitBoard(((x & 0xF0F0_F0F0_F0F0_F0F0) >> 4) | ((x & 0x0F0F_0F0F_0F0F_0F0F) << 4))
+    }
+
+    /// Board rotated or reflected the same way as Symmetry::apply does
+    pub fn transform(self, sym: Symmetry) -> Self {
+        let (transposed, mirrored_v, mirrored_h) = sym.steps();
+        let mut bits = self;
+        if transposed {
+            bits = bits.transpose();
+        }
+        if mirrored_v {
+            bits = bits.mirror_v();
+        }
+        if mirrored_h {
+            bits = bits.mirror_h();
+        }
+        bits
+    }
+
+    /// Symmetric board with the least value and symmetry giving it
+    pub fn canonical(self) -> (Self, Symmetry) {
+        Symmetry::ALL
+            .iter()
+            .map(|&sym| (self.transform(sym), sym))
+            .min_by_key(|(bits, _)| bits.0)
+            .unwrap()
+    }
+
+    /// Slide board to specific side, returns new board and score of merges
+    pub fn slide_to(self, dir: Direction) -> (Self, u32) {
+        let tables = tables();
+        let mut x = self.0;
+        let mut score = 0;
+        match dir {
+            Direction::Left | Direction::Right => {
+                let (moves, scores) = if dir == Direction::Left {
+                    (&tables.row_left, &tables.score_left)
+                } else {
+                    (&tables.row_right, &tables.score_right)
+                };
+                for j in 0..BOARD_SIZE {
+                    let shift = 48 - 16 * j;
+                    let row = ((self.0 >> shift) & ROW_MASK) as usize;
+                    x ^= moves[row] << shift;
+                    score += scores[row];
+                }
+            }
+            Direction::Up | Direction::Down => {
+                let (moves, scores) = if dir == Direction::Up {
+                    (&tables.col_up, &tables.score_left)
+                } else {
+                    (&tables.col_down, &tables.score_right)
+                };
+                //columns are rows of transposed board
+                let t = self.transpose().0;
+                for i in 0..BOARD_SIZE {
+                    let column = ((t >> (48 - 16 * i)) & ROW_MASK) as usize;
+                    x ^= moves[column] >> (4 * i);
+                    score += scores[column];
+                }
+            }
+        }
+        (BitBoard(x), score)
+    }
+
+    /// Exponent of the cell, 0 - empty
+    pub fn cell(self, j: usize, i: usize) -> u8 {
+        ((self.0 >> cell_shift(j, i)) & 0xF) as u8
+    }
+
+    /// Copy with the cell replaced, exponent below 16
+    pub fn with_cell(self, j: usize, i: usize, exp: u8) -> Self {
+        let shift = cell_shift(j, i);
+        BitBoard((self.0 & !(0xF << shift)) | ((exp as u64 & 0xF) << shift))
+    }
+
+    /// Count the number of empty cells
+    pub fn empty_count(self) -> u8 {
+        zero_cells(self.0).count_ones() as u8
+    }
+
+    /// The biggest exponent
+    pub fn max_exp(self) -> u8 {
+        let mut x = self.0;
+        let mut max = 0;
+        while x != 0 {
+            max = max.max((x & 0xF) as u8);
+            x >>= 4;
+        }
+        max
+    }
+
+    /// Count the number of different non-empty cells
+    pub fn distinct_count(self) -> u8 {
+        let mut x = self.0;
+        let mut mask = 0u16;
+        for _ in 0..BOARD_SIZE * BOARD_SIZE {
+            mask |= 1 << (x & 0xF);
+            x >>= 4;
+        }
+        //zero is empty cell
+        (mask & !1).count_ones() as u8
+    }
+
+    /// Is it possible to merge cells in a specific plane
+    pub fn can_merge(self, dir: Direction) -> bool {
+        let x = self.0;
+        //equal neighbours have zero difference
+        match dir {
+            Direction::Left | Direction::Right => zero_cells(x ^ (x >> 4)) & HORIZONTAL_PAIRS != 0,
+            Direction::Up | Direction::Down => zero_cells(x ^ (x >> 16)) & VERTICAL_PAIRS != 0,
+        }
+    }
+
+    /// Is any move possible
+    pub fn can_move(self) -> bool {
+        self.empty_count() > 0 || self.can_merge(Direction::Left) || self.can_merge(Direction::Up)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board;
+    use std::thread;
+
+    impl BitBoard {
+        fn from_matrix(m: &[[u8; BOARD_SIZE]; BOARD_SIZE]) -> Self {
+            BitBoard(matrix::to_u64(m))
+        }
+
+        fn to_matrix(self) -> [[u8; BOARD_SIZE]; BOARD_SIZE] {
+            matrix::from_u64(self.0)
+        }
+    }
+
+    #[rustfmt::skip]
+    const BOARD: [[u8; BOARD_SIZE]; BOARD_SIZE] =
+       [[1, 1, 2, 0],
+        [0, 2, 2, 3],
+        [1, 0, 1, 3],
+        [4, 4, 4, 4]];
+
+    #[test]
+    fn should_convert_matrix() {
+        let bits = BitBoard::from_matrix(&BOARD);
+        assert_eq!(bits.0, 0x1120_0223_1013_4444);
+        assert_eq!(bits.to_matrix(), BOARD);
+    }
+
+    #[test]
+    fn should_transpose() {
+        let mut expected = BOARD;
+        matrix::transpose(&mut expected);
+        let actual = BitBoard::from_matrix(&BOARD).transpose();
+        assert_eq!(actual.to_matrix(), expected);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn should_slide_all_directions() {
+        board::load_cache();
+        let bits = BitBoard::from_matrix(&BOARD);
+
+        let (left, score) = bits.slide_to(Direction::Left);
+        assert_eq!(left.to_matrix(),
+           [[2, 2, 0, 0],
+            [3, 3, 0, 0],
+            [2, 3, 0, 0],
+            [5, 5, 0, 0]]);
+        assert_eq!(score, 4 + 8 + 4 + 32 + 32);
+
+        let (right, score) = bits.slide_to(Direction::Right);
+        assert_eq!(right.to_matrix(),
+           [[0, 0, 2, 2],
+            [0, 0, 3, 3],
+            [0, 0, 2, 3],
+            [0, 0, 5, 5]]);
+        assert_eq!(score, 4 + 8 + 4 + 32 + 32);
+
+        let (up, score) = bits.slide_to(Direction::Up);
+        assert_eq!(up.to_matrix(),
+           [[2, 1, 3, 4],
+            [4, 2, 1, 4],
+            [0, 4, 4, 0],
+            [0, 0, 0, 0]]);
+        assert_eq!(score, 4 + 8 + 16);
+
+        let (down, score) = bits.slide_to(Direction::Down);
+        assert_eq!(down.to_matrix(),
+           [[0, 0, 0, 0],
+            [0, 1, 3, 0],
+            [2, 2, 1, 4],
+            [4, 4, 4, 4]]);
+        assert_eq!(score, 4 + 8 + 16);
+    }
+
+    #[test]
+    fn should_transform_as_matrix() {
+        let bits = BitBoard::from_matrix(&BOARD);
+        for sym in Symmetry::ALL {
+            assert_eq!(bits.transform(sym).to_matrix(), sym.apply(&BOARD));
+        }
+    }
+
+    #[test]
+    fn should_find_same_canonical() {
+        let bits = BitBoard::from_matrix(&BOARD);
+        let (canonical, sym) = bits.canonical();
+        assert_eq!(bits.transform(sym), canonical);
+        for other in Symmetry::ALL {
+            assert_eq!(bits.transform(other).canonical().0, canonical);
+        }
+    }
+
+    #[test]
+    fn should_init_tables_from_many_threads() {
+        //no explicit cache loading
+        let handles: Vec<_> = (0..8)
+            .map(|_| thread::spawn(|| BitBoard::from_matrix(&BOARD).slide_to(Direction::Left)))
+            .collect();
+        for handle in handles {
+            let (left, score) = handle.join().unwrap();
+            assert_eq!(left.0, 0x2200_3300_2300_5500);
+            assert_eq!(score, 4 + 8 + 4 + 32 + 32);
+        }
+    }
+
+    #[test]
+    fn should_not_move_fixed_row() {
+        board::load_cache();
+        let bits = BitBoard(0x1234_0000_0000_0000);
+        assert_eq!(bits.slide_to(Direction::Left).0, bits);
+        assert_eq!(bits.slide_to(Direction::Up).0, bits);
+    }
+
+    #[test]
+    fn should_read_and_write_cells() {
+        let bits = BitBoard::from_matrix(&BOARD);
+        assert_eq!(bits.cell(1, 3), 3);
+        assert_eq!(bits.cell(3, 0), 4);
+        assert_eq!(bits.max_exp(), 4);
+        assert_eq!(bits.distinct_count(), 4);
+
+        let mut expected = BOARD;
+        expected[2][1] = 14;
+        assert_eq!(bits.with_cell(2, 1, 14).to_matrix(), expected);
+        assert_eq!(bits.with_cell(2, 1, 14).max_exp(), 14);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn should_count_empty_and_moves() {
+        assert_eq!(BitBoard::from_matrix(&BOARD).empty_count(), 3);
+        assert_eq!(BitBoard(0).empty_count(), 16);
+
+        let full =
+           [[1, 2, 1, 2],
+            [2, 1, 2, 1],
+            [1, 2, 1, 2],
+            [2, 1, 2, 1]];
+        assert!(!BitBoard::from_matrix(&full).can_move());
+
+        let mut horizontal = full;
+        horizontal[0][0] = 3;
+        horizontal[0][1] = 3;
+        assert!(BitBoard::from_matrix(&horizontal).can_move());
+        assert!(BitBoard::from_matrix(&horizontal).can_merge(Direction::Right));
+        assert!(!BitBoard::from_matrix(&horizontal).can_merge(Direction::Up));
+
+        let mut vertical = full;
+        vertical[0][0] = 3;
+        vertical[1][0] = 3;
+        assert!(BitBoard::from_matrix(&vertical).can_move());
+
+        //last cell of a row and first of the next are not neighbours
+        let mut wrapped = full;
+        wrapped[0][3] = 3;
+        wrapped[1][0] = 3;
+        assert!(!BitBoard::from_matrix(&wrapped).can_move());
+    }
+}
@@ -17,104 +17,246 @@ You should have received a copy of the GNU General Public License
 along with game-2048-engine.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use crate::cache::lazy::Lazy;
-use crate::cache::slide_cache::SlideCache;
+use crate::bitboard::BitBoard;
+use crate::cache::slide_cache::{self, SlideCache};
+pub use crate::cache::slide_cache::{CacheError, FILE as CACHE_FILE};
 use crate::direction::Direction;
 use crate::matrix;
+use crate::symmetry::Symmetry;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
 
-#[derive(Debug, Clone, Copy, Default)]
-pub struct Board {
-    pub(super) board: [[u8; BOARD_SIZE]; BOARD_SIZE],
+/// Square board of 'N' x 'N' cells
+#[derive(Debug, Clone, Copy)]
+pub struct Board<const N: usize = BOARD_SIZE> {
+    cells: Cells<N>,
     pub state: State,
     pub score: u32,
     pub move_count: u16,
 }
 
+/// Default size, the only one with lookup tables
 pub const BOARD_SIZE: usize = 4;
-static SLIDE_CACHE: Lazy<SlideCache> = Lazy::new(SlideCache::new());
+/// The biggest exponent fitting into 4 bits, 32768
+const MAX_PACKED_EXP: u8 = 15;
+static SLIDE_CACHE: OnceLock<SlideCache> = OnceLock::new();
 
+/// Exponents of tiles, 0 - empty cell
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cells<const N: usize> {
+    /// 4x4 board moved with lookup tables, no cell may become 65536
+    Packed(BitBoard),
+    /// Other sizes and 4x4 boards from 32768
+    Grid([[u8; N]; N]),
+}
+
+impl<const N: usize> Cells<N> {
+    /// Packed whenever lookup tables can move the board
+    fn from_matrix(m: [[u8; N]; N]) -> Self {
+        if N == BOARD_SIZE && matrix::max_cell(&m) < MAX_PACKED_EXP {
+            Cells::Packed(BitBoard(matrix::to_u64(&m)))
+        } else {
+            Cells::Grid(m)
+        }
+    }
+
+    /// Packed board after a move, the biggest packed tile makes it grid
+    fn from_packed(bits: BitBoard) -> Self {
+        if bits.max_exp() < MAX_PACKED_EXP {
+            Cells::Packed(bits)
+        } else {
+            Cells::Grid(matrix::from_u64(bits.0))
+        }
+    }
+}
+
+/// Build cache in memory ahead, otherwise it's built by the first move
 pub fn load_cache() {
-    SLIDE_CACHE.set(SlideCache::load_cache());
+    slide_cache();
+}
+
+/// Read cache made by 'create_cache' instead of building it
+/// returns: false if cache is already initialized
+pub fn load_cache_file<P: AsRef<Path>>(path: P) -> Result<bool, CacheError> {
+    if SLIDE_CACHE.get().is_some() {
+        return Ok(false);
+    }
+    let cache = SlideCache::load_cache(path.as_ref())?;
+    Ok(SLIDE_CACHE.set(cache).is_ok())
 }
 
-pub fn create_cache() {
-    SlideCache::create_cache();
+/// Save cache file, 'CACHE_FILE' by default
+pub fn create_cache<P: AsRef<Path>>(path: P) -> Result<(), CacheError> {
+    SlideCache::create_cache(path.as_ref())
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+/// Cache shared by all threads, initialized once
+pub(crate) fn slide_cache() -> &'static SlideCache {
+    SLIDE_CACHE.get_or_init(SlideCache::generate)
+}
+
+/// Wrong tile values or grid shape
+#[derive(Debug, PartialEq, Eq)]
+pub enum BoardError {
+    /// Tile is neither empty nor a power of two from 2
+    NotPowerOfTwo(u64),
+    /// Tile can't be made on the board of this size
+    TooBig(u64),
+    /// Cell text is not a number
+    InvalidNumber(String),
+    RowCount(usize),
+    RowLength {
+        row: usize,
+        len: usize,
+    },
+}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BoardError::NotPowerOfTwo(value) => write!(f, "{} is not a power of two", value),
+            BoardError::TooBig(value) => write!(f, "tile {} is too big for the board", value),
+            BoardError::InvalidNumber(text) => write!(f, "'{}' is not a tile", text),
+            BoardError::RowCount(count) => write!(f, "wrong number of rows {}", count),
+            BoardError::RowLength { row, len } => {
+                write!(f, "wrong number of cells {} in row {}", len, row + 1)
+            }
+        }
+    }
+}
+
+impl Error for BoardError {}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
 pub enum State {
+    #[default]
     InGame,
+    /// Target tile is reached, moves are stopped unless keep playing
+    Win,
     Lose,
 }
 
-impl Default for State {
+impl<const N: usize> Default for Board<N> {
     fn default() -> Self {
-        State::InGame
+        Board::new()
     }
 }
 
-impl Board {
+impl<const N: usize> Board<N> {
     /// Create Empty Board
     pub fn new() -> Self {
         Board {
-            board: [[0u8; BOARD_SIZE]; BOARD_SIZE],
+            cells: Cells::from_matrix([[0u8; N]; N]),
             state: State::InGame,
             score: 0,
             move_count: 0,
         }
     }
 
-    /// Slide board to specific side
-    pub fn slide_to(&mut self, dir: Direction) -> bool {
-        let moved = match dir {
-            Direction::Left => self.slide_board(matrix::to_u16, matrix::from_u16),
-            Direction::Right => self.slide_board(matrix::to_u16_rev, matrix::from_u16_rev),
-            Direction::Up => {
-                matrix::transpose(&mut self.board);
-                let moved = self.slide_board(matrix::to_u16, matrix::from_u16);
-                matrix::transpose(&mut self.board);
-                moved
+    /// Create board from tile values, 0 - empty cell.
+    /// Score and move count start from zero
+    pub fn from_tiles(tiles: [[u64; N]; N]) -> Result<Self, BoardError> {
+        let mut cells = [[0u8; N]; N];
+        for (j, row) in tiles.iter().enumerate() {
+            for (i, &value) in row.iter().enumerate() {
+                cells[j][i] = exponent::<N>(value)?;
             }
-            Direction::Down => {
-                matrix::transpose(&mut self.board);
-                let moved = self.slide_board(matrix::to_u16_rev, matrix::from_u16_rev);
-                matrix::transpose(&mut self.board);
-                moved
+        }
+        let mut board = Board::new();
+        board.set_cells(cells);
+        if !board.can_move() {
+            board.state = State::Lose;
+        }
+        Ok(board)
+    }
+
+    /// Tile values, 0 - empty cell
+    pub fn tiles(&self) -> [[u64; N]; N] {
+        let mut tiles = [[0u64; N]; N];
+        for (j, row) in self.cells().iter().enumerate() {
+            for (i, &cell) in row.iter().enumerate() {
+                if cell != 0 {
+                    tiles[j][i] = 1 << cell;
+                }
             }
-        };
+        }
+        tiles
+    }
 
-        if moved {
-            self.move_count += 1;
+    /// Exponents of tiles, 0 - empty cell
+    pub(crate) fn cells(&self) -> [[u8; N]; N] {
+        match self.cells {
+            Cells::Packed(bits) => matrix::from_u64(bits.0),
+            Cells::Grid(m) => m,
         }
-        moved
     }
 
-    /// Slide board with cache get functions
-    fn slide_board(
-        &mut self,
-        encode_fn: fn([u8; BOARD_SIZE]) -> u16,
-        decode_fn: fn(&mut [u8; BOARD_SIZE], u16),
-    ) -> bool {
-        let mut moved = false;
-        for j in 0..BOARD_SIZE {
-            let id = encode_fn(self.board[j]);
-            let item = SLIDE_CACHE.get().table[id as usize];
-            if item.line != 0 {
-                decode_fn(&mut self.board[j], item.line);
-                self.score += if item.score == 0 {
-                    0
-                } else {
-                    (item.score + 1) as u32
-                };
-                moved = true;
+    /// Replace all cells, score and counters are kept
+    pub(crate) fn set_cells(&mut self, cells: [[u8; N]; N]) {
+        self.cells = Cells::from_matrix(cells);
+    }
+
+    /// Exponent of the cell, 0 - empty
+    pub(crate) fn cell(&self, j: usize, i: usize) -> u8 {
+        match self.cells {
+            Cells::Packed(bits) => bits.cell(j, i),
+            Cells::Grid(ref m) => m[j][i],
+        }
+    }
+
+    pub(crate) fn set_cell(&mut self, j: usize, i: usize, exp: u8) {
+        match self.cells {
+            Cells::Packed(bits) if exp < MAX_PACKED_EXP => {
+                self.cells = Cells::Packed(bits.with_cell(j, i, exp));
+            }
+            _ => {
+                let mut m = self.cells();
+                m[j][i] = exp;
+                self.set_cells(m);
             }
         }
+    }
 
+    /// Boards have the same tiles, score and move count may differ
+    pub(crate) fn same_cells(&self, other: &Board<N>) -> bool {
+        //representation depends only on tiles
+        self.cells == other.cells
+    }
+
+    /// Packed state of 4x4 boards without big tiles
+    pub(crate) fn packed(&self) -> Option<BitBoard> {
+        match self.cells {
+            Cells::Packed(bits) => Some(bits),
+            Cells::Grid(_) => None,
+        }
+    }
+
+    /// Slide board to specific side
+    pub fn slide_to(&mut self, dir: Direction) -> bool {
+        let (moved, score) = match self.cells {
+            Cells::Packed(bits) => {
+                let (moved_bits, score) = bits.slide_to(dir);
+                self.cells = Cells::from_packed(moved_bits);
+                (moved_bits != bits, score)
+            }
+            Cells::Grid(ref mut m) => slide_rows(m, dir),
+        };
+
+        if moved {
+            self.score += score;
+            self.move_count += 1;
+        }
         moved
     }
 
     /// Is any move possible
     pub fn can_move(&self) -> bool {
+        if let Cells::Packed(bits) = self.cells {
+            return bits.can_move();
+        }
         self.empty_count() > 0u8
             // two perpendicular enough
             || self.can_move_dir(Direction::Left)
@@ -123,10 +265,14 @@ impl Board {
 
     /// Is it possible to merge cells in a specific plane
     pub fn can_move_dir(&self, dir: Direction) -> bool {
+        let m = match self.cells {
+            Cells::Packed(bits) => return bits.can_merge(dir),
+            Cells::Grid(ref m) => m,
+        };
         let (x, y) = dir.get_mask();
-        for j in 0..BOARD_SIZE - y {
-            for i in 0..BOARD_SIZE - x {
-                if self.board[j][i] == self.board[j + y][i + x] {
+        for j in 0..N - y {
+            for i in 0..N - x {
+                if m[j][i] == m[j + y][i + x] {
                     return true;
                 }
             }
@@ -138,11 +284,11 @@ impl Board {
     pub fn set_move(&mut self, game_move: (u8, u8)) {
         let (value, pos) = game_move;
         let mut c = 0u8;
-        'outer: for j in 0..BOARD_SIZE {
-            for i in 0..BOARD_SIZE {
-                if self.board[j][i] == 0 {
+        'outer: for j in 0..N {
+            for i in 0..N {
+                if self.cell(j, i) == 0 {
                     if c == pos {
-                        self.board[j][i] = value;
+                        self.set_cell(j, i, value);
                         break 'outer;
                     }
                     c += 1;
@@ -154,16 +300,249 @@ impl Board {
 
     /// Count the number of empty cells
     pub fn empty_count(&self) -> u8 {
-        matrix::empty_count(&self.board)
+        match self.cells {
+            Cells::Packed(bits) => bits.empty_count(),
+            Cells::Grid(ref m) => matrix::empty_count(m),
+        }
+    }
+
+    /// Count the number of different tiles
+    pub fn distinct_count(&self) -> u8 {
+        match self.cells {
+            Cells::Packed(bits) => bits.distinct_count(),
+            Cells::Grid(ref m) => matrix::distinct_count(m),
+        }
     }
 
     /// The value of the maximum cell
-    pub fn max_cell(&self) -> u16 {
-        1 << matrix::max_cell(&self.board)
+    pub fn max_cell(&self) -> u64 {
+        let exp = match self.cells {
+            Cells::Packed(bits) => bits.max_exp(),
+            Cells::Grid(ref m) => matrix::max_cell(m),
+        };
+        1 << exp
+    }
+
+    /// Rotated or reflected copy, score and counters are kept
+    pub fn transform(&self, sym: Symmetry) -> Board<N> {
+        let cells = match self.cells {
+            Cells::Packed(bits) => Cells::Packed(bits.transform(sym)),
+            Cells::Grid(ref m) => Cells::Grid(sym.apply(m)),
+        };
+        Board { cells, ..*self }
     }
 
-    /// Get board as u64 value
+    /// The board among all symmetric ones having the least id and symmetry giving it,
+    /// directions of the canonical board are mapped with the same symmetry
+    pub fn canonical(&self) -> (Board<N>, Symmetry) {
+        let sym = match self.cells {
+            Cells::Packed(bits) => bits.canonical().1,
+            Cells::Grid(_) => *Symmetry::ALL
+                .iter()
+                .min_by_key(|sym| self.transform(**sym).get_board_id())
+                .unwrap(),
+        };
+        (self.transform(sym), sym)
+    }
+
+    /// Id of the canonical board, the same for all symmetric boards
+    pub fn get_canonical_id(&self) -> u64 {
+        match self.cells {
+            Cells::Packed(bits) => bits.canonical().0 .0,
+            Cells::Grid(_) => self.canonical().0.get_board_id(),
+        }
+    }
+
+    /// Get board as u64 value, boards bigger than 4x4 or with cells from 65536 are hashed
     pub fn get_board_id(&self) -> u64 {
-        matrix::to_u64(&self.board)
+        let m = match self.cells {
+            Cells::Packed(bits) => return bits.0,
+            Cells::Grid(ref m) => m,
+        };
+        if N <= BOARD_SIZE && matrix::max_cell(m) <= MAX_PACKED_EXP {
+            return matrix::to_u64(m);
+        }
+        //FNV-1a
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for row in m {
+            for cell in row {
+                hash = (hash ^ *cell as u64).wrapping_mul(0x0000_0100_0000_01b3);
+            }
+        }
+        hash
+    }
+}
+
+/// Slide every row separately, sizes without lookup tables
+/// returns: change has been made, score of merges
+fn slide_rows<const N: usize>(m: &mut [[u8; N]; N], dir: Direction) -> (bool, u32) {
+    let transposed = matches!(dir, Direction::Up | Direction::Down);
+    let reversed = matches!(dir, Direction::Right | Direction::Down);
+
+    if transposed {
+        matrix::transpose(m);
+    }
+    let mut moved = false;
+    let mut score = 0;
+    for row in m.iter_mut() {
+        if reversed {
+            row.reverse();
+        }
+        if let Some(row_score) = slide_cache::slide_row(row) {
+            score += row_score;
+            moved = true;
+        }
+        if reversed {
+            row.reverse();
+        }
+    }
+    if transposed {
+        matrix::transpose(m);
+    }
+    (moved, score)
+}
+
+/// Exponent of tile value, all tiles merged together is the biggest one
+pub(crate) fn exponent<const N: usize>(value: u64) -> Result<u8, BoardError> {
+    if value == 0 {
+        return Ok(0);
+    }
+    if value == 1 || !value.is_power_of_two() {
+        return Err(BoardError::NotPowerOfTwo(value));
+    }
+    let exp = value.trailing_zeros() as usize;
+    if exp > N * N + 1 {
+        return Err(BoardError::TooBig(value));
+    }
+    Ok(exp as u8)
+}
+
+/// Rows of tile values separated by whitespace, '0' or '.' - empty cell
+impl<const N: usize> FromStr for Board<N> {
+    type Err = BoardError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rows: Vec<&str> = s.lines().filter(|line| !line.trim().is_empty()).collect();
+        if rows.len() != N {
+            return Err(BoardError::RowCount(rows.len()));
+        }
+
+        let mut tiles = [[0u64; N]; N];
+        for (j, line) in rows.iter().enumerate() {
+            let cells: Vec<&str> = line.split_whitespace().collect();
+            if cells.len() != N {
+                return Err(BoardError::RowLength {
+                    row: j,
+                    len: cells.len(),
+                });
+            }
+            for (i, cell) in cells.iter().enumerate() {
+                tiles[j][i] = match *cell {
+                    "." => 0,
+                    _ => cell
+                        .parse()
+                        .map_err(|_| BoardError::InvalidNumber(cell.to_string()))?,
+                };
+            }
+        }
+        Board::from_tiles(tiles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[rustfmt::skip]
+    fn should_parse_grid() {
+        let board: Board = "
+            2 .  4    0
+            . 8  .    .
+            0 0  2048 0
+            . 16 .    131072"
+            .parse()
+            .unwrap();
+        assert_eq!(board.cells(),
+           [[1, 0, 2, 0],
+            [0, 3, 0, 0],
+            [0, 0, 11, 0],
+            [0, 4, 0, 17]]);
+        assert_eq!(board.state, State::InGame);
+        assert_eq!(board.score, 0);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn should_keep_4x4_board_packed() {
+        load_cache();
+        let mut board = Board::new();
+        board.set_cells(
+           [[14, 14, 0, 0],
+            [1, 0, 0, 0],
+            [0, 0, 0, 0],
+            [0, 0, 0, 1]]);
+        assert!(board.packed().is_some());
+        assert_eq!(board.empty_count(), 12);
+        assert_eq!(board.distinct_count(), 2);
+
+        board.set_move((2, 0));
+        assert_eq!(board.cell(0, 2), 2);
+        assert!(board.packed().is_some());
+
+        //32768 can't be merged by lookup tables
+        assert!(board.slide_to(Direction::Left));
+        assert!(board.packed().is_none());
+        assert_eq!(board.cells()[0], [15, 2, 0, 0]);
+        assert_eq!(board.max_cell(), 32768);
+        assert_eq!(board.score, 32768);
+
+        let small: Board<3> = Board::new();
+        assert!(small.packed().is_none());
+    }
+
+    #[test]
+    fn should_print_and_parse_back() {
+        let board: Board<3> = Board::from_tiles([[2, 0, 4], [0, 1024, 0], [8, 0, 0]]).unwrap();
+        let text = board.to_string();
+        assert_eq!(text.lines().next(), Some("     2      0      4"));
+
+        let parsed: Board<3> = text.parse().unwrap();
+        assert_eq!(parsed.tiles(), board.tiles());
+    }
+
+    #[test]
+    fn should_detect_lost_board() {
+        let board: Board<2> = "2 4\n4 2".parse().unwrap();
+        assert_eq!(board.state, State::Lose);
+    }
+
+    #[test]
+    fn should_reject_wrong_grid() {
+        let parse = |s: &str| s.parse::<Board>().map(|board| board.tiles());
+        assert_eq!(
+            parse("2 0 0 0\n0 0 0 0\n0 0 0 0"),
+            Err(BoardError::RowCount(3))
+        );
+        assert_eq!(
+            parse("2 0 0 0\n0 0 0\n0 0 0 0\n0 0 0 0"),
+            Err(BoardError::RowLength { row: 1, len: 3 })
+        );
+        assert_eq!(
+            parse("3 0 0 0\n0 0 0 0\n0 0 0 0\n0 0 0 0"),
+            Err(BoardError::NotPowerOfTwo(3))
+        );
+        assert_eq!(
+            parse("1 0 0 0\n0 0 0 0\n0 0 0 0\n0 0 0 0"),
+            Err(BoardError::NotPowerOfTwo(1))
+        );
+        assert_eq!(
+            parse("x 0 0 0\n0 0 0 0\n0 0 0 0\n0 0 0 0"),
+            Err(BoardError::InvalidNumber("x".to_string()))
+        );
+        assert_eq!(
+            Board::<2>::from_tiles([[64, 0], [0, 0]]).map(|board| board.tiles()),
+            Err(BoardError::TooBig(64))
+        );
     }
 }
@@ -17,5 +17,4 @@ You should have received a copy of the GNU General Public License
 along with game-2048-engine.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-pub mod lazy;
 pub mod slide_cache;
@@ -18,20 +18,31 @@ along with game-2048-engine.  If not, see <https://www.gnu.org/licenses/>.
 */
 
 use crate::matrix;
+use std::error::Error;
+use std::fmt;
 use std::fs::File;
-use std::io::BufReader;
+use std::io;
 use std::io::BufWriter;
 use std::io::Read;
-use std::io::Result;
 use std::io::Write;
+use std::path::Path;
 
 pub struct SlideCache {
     // 45093 zero entry, 20443 actual entry
-    pub table: [SlideLine; 65536],
+    pub table: Vec<SlideLine>,
 }
 
 const ACTUAL_SIZE: usize = 20443;
-const FILE: &str = "slide-cache.bin";
+pub const FILE: &str = "slide-cache.bin";
+
+/// File header: magic, version, board size, max exponent, entry count
+const MAGIC: [u8; 4] = *b"2048";
+const VERSION: u16 = 1;
+const ROW_SIZE: u8 = 4;
+const MAX_EXP: u8 = 15;
+const HEADER_SIZE: usize = 12;
+const RECORD_SIZE: usize = 5;
+const CHECKSUM_SIZE: usize = 4;
 
 #[derive(Debug, Clone, Copy)]
 pub struct SlideLine {
@@ -42,16 +53,78 @@ pub struct SlideLine {
     pub score: u16,
 }
 
+/// Error of reading or writing cache file
+#[derive(Debug)]
+pub enum CacheError {
+    Io(io::Error),
+    /// File ends before the checksum
+    Truncated,
+    /// Not a cache file
+    BadMagic,
+    UnsupportedVersion(u16),
+    /// Cache made for other rows
+    Incompatible {
+        row_size: u8,
+        max_exp: u8,
+    },
+    /// Content doesn't match the checksum
+    Checksum {
+        expected: u32,
+        actual: u32,
+    },
+    /// Checksum is right, but records are not
+    Corrupted,
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CacheError::Io(err) => write!(f, "cache file error: {}", err),
+            CacheError::Truncated => write!(f, "cache file is truncated"),
+            CacheError::BadMagic => write!(f, "not a slide cache file"),
+            CacheError::UnsupportedVersion(version) => {
+                write!(f, "unsupported cache version {}", version)
+            }
+            CacheError::Incompatible { row_size, max_exp } => write!(
+                f,
+                "cache for rows of {} cells up to exponent {}",
+                row_size, max_exp
+            ),
+            CacheError::Checksum { expected, actual } => write!(
+                f,
+                "cache checksum {:#010x} doesn't match {:#010x}",
+                actual, expected
+            ),
+            CacheError::Corrupted => write!(f, "cache records are corrupted"),
+        }
+    }
+}
+
+impl Error for CacheError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CacheError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CacheError {
+    fn from(err: io::Error) -> Self {
+        CacheError::Io(err)
+    }
+}
+
 impl SlideCache {
-    /// Create empty cache
-    pub const fn new() -> Self {
+    /// Create empty cache, on heap as too big for thread stack
+    pub fn new() -> Self {
         SlideCache {
-            table: [SlideLine { line: 0, score: 0 }; 65536],
+            table: vec![SlideLine { line: 0, score: 0 }; 65536],
         }
     }
 
-    /// Create and save info file
-    pub fn create_cache() {
+    /// Build every row in memory
+    pub fn generate() -> Self {
         let mut cache = SlideCache::new();
         for a in 0..16 {
             for b in 0..16 {
@@ -68,74 +141,130 @@ impl SlideCache {
             }
         }
         cache
-            .serialize()
-            .expect(concat!("cannot write file ", "slide-cache.bin"));
+    }
+
+    /// Create and save info file
+    pub fn create_cache(path: &Path) -> Result<(), CacheError> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(&SlideCache::generate().serialize())?;
+        file.flush()?;
+        Ok(())
     }
 
     /// Load cache from file
-    pub fn load_cache() -> Self {
-        let mut cache = SlideCache::new();
-        cache
-            .deserialize()
-            .expect(concat!("cannot read file ", "slide-cache.bin"));
-        cache
+    pub fn load_cache(path: &Path) -> Result<Self, CacheError> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        SlideCache::deserialize(&bytes)
     }
 
-    /// Write array to file
-    fn serialize(&self) -> Result<()> {
-        let mut file = BufWriter::new(File::create(FILE)?);
-        let mut buf: [u8; 5] = [0u8; 5];
+    /// Header, records of moved rows, checksum of all before
+    fn serialize(&self) -> Vec<u8> {
+        let count = self.table.iter().filter(|item| item.line != 0).count();
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + ACTUAL_SIZE * RECORD_SIZE + CHECKSUM_SIZE);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&VERSION.to_be_bytes());
+        bytes.push(ROW_SIZE);
+        bytes.push(MAX_EXP);
+        bytes.extend_from_slice(&(count as u32).to_be_bytes());
+
         let mut delta = 0;
         for i in 0..self.table.len() {
             if self.table[i].line != 0 {
-                buf[0] = (i - delta) as u8; //delta encoding
+                //delta encoding, gaps between moved rows are short
+                bytes.push((i - delta) as u8);
                 delta = i;
-                let score = self.table[i].score;
-                /*let score = if self.table[i].score == 0 {
-                    0
-                } else {
-                    self.table[i].score - 1
-                };*/
-                buf[1] = (self.table[i].line >> 8) as u8;
-                buf[2] = self.table[i].line as u8;
-                buf[3] = (score >> 8) as u8;
-                buf[4] = score as u8;
-                file.write_all(&buf)?;
+                bytes.extend_from_slice(&self.table[i].line.to_be_bytes());
+                bytes.extend_from_slice(&self.table[i].score.to_be_bytes());
             }
         }
-        file.flush()?;
-        Ok(())
+        let checksum = checksum(&bytes);
+        bytes.extend_from_slice(&checksum.to_be_bytes());
+        bytes
     }
 
-    /// Read cache from file
-    fn deserialize(&mut self) -> Result<()> {
-        let mut file = BufReader::new(File::open(FILE)?);
-        let mut buf: [u8; 5] = [0u8; 5];
+    /// Check header and checksum before the records
+    fn deserialize(bytes: &[u8]) -> Result<Self, CacheError> {
+        if bytes.len() < MAGIC.len() {
+            return Err(CacheError::Truncated);
+        }
+        if bytes[0..4] != MAGIC {
+            return Err(CacheError::BadMagic);
+        }
+        if bytes.len() < HEADER_SIZE + CHECKSUM_SIZE {
+            return Err(CacheError::Truncated);
+        }
+        let version = u16::from_be_bytes([bytes[4], bytes[5]]);
+        if version != VERSION {
+            return Err(CacheError::UnsupportedVersion(version));
+        }
+        let (row_size, max_exp) = (bytes[6], bytes[7]);
+        if row_size != ROW_SIZE || max_exp != MAX_EXP {
+            return Err(CacheError::Incompatible { row_size, max_exp });
+        }
+        let count = u32::from_be_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize;
+        let end = HEADER_SIZE + count * RECORD_SIZE;
+        if bytes.len() < end + CHECKSUM_SIZE {
+            return Err(CacheError::Truncated);
+        }
+        if bytes.len() > end + CHECKSUM_SIZE {
+            return Err(CacheError::Corrupted);
+        }
+
+        let expected =
+            u32::from_be_bytes([bytes[end], bytes[end + 1], bytes[end + 2], bytes[end + 3]]);
+        let actual = checksum(&bytes[..end]);
+        if expected != actual {
+            return Err(CacheError::Checksum { expected, actual });
+        }
+
+        let mut cache = SlideCache::new();
         let mut delta = 0usize;
-        for _i in 0..ACTUAL_SIZE {
-            file.read_exact(&mut buf)?;
-            delta += buf[0] as usize;
-            let line = ((buf[1] as u16) << 8) + buf[2] as u16;
-            let score = ((buf[3] as u16) << 8) + buf[4] as u16;
-            self.table[delta] = SlideLine {
-                line,
-                //score: if score == 0 { 0 } else { score + 1 },
-                score,
-            };
+        for record in bytes[HEADER_SIZE..end].chunks(RECORD_SIZE) {
+            delta += record[0] as usize;
+            let line = u16::from_be_bytes([record[1], record[2]]);
+            let score = u16::from_be_bytes([record[3], record[4]]);
+            if delta >= cache.table.len() || line == 0 {
+                return Err(CacheError::Corrupted);
+            }
+            cache.table[delta] = SlideLine { line, score };
         }
-        Ok(())
+        Ok(cache)
     }
 }
 
+/// FNV-1a 32 bit
+fn checksum(bytes: &[u8]) -> u32 {
+    let mut hash: u32 = 0x811c_9dc5;
+    for byte in bytes {
+        hash = (hash ^ *byte as u32).wrapping_mul(0x0100_0193);
+    }
+    hash
+}
+
 /// slide one line
 fn slide_array(mut m: [u8; 4]) -> Option<SlideLine> {
+    let score = slide_row(&mut m)?;
+    // line doesn't fit into u16, merges into 65536 are slided by board itself
+    if m.iter().any(|&cell| cell > 15) {
+        return None;
+    }
+    Some(SlideLine {
+        line: matrix::to_u16(m),
+        score: if score == 0 { 0 } else { (score - 1) as u16 },
+    })
+}
+
+/// Slide one line of any size to the start
+/// returns: score of merges if moved
+pub(crate) fn slide_row<const N: usize>(m: &mut [u8; N]) -> Option<u32> {
     let mut moved = false;
     let mut score: u32 = 0;
-    for i in 0..3 {
+    for i in 0..N.saturating_sub(1) {
         // move next non zero to current
         if m[i] == 0 {
             // from current to end of line
-            for k in i + 1..4 {
+            for k in i + 1..N {
                 if m[k] != 0 {
                     m[i] = m[k];
                     m[k] = 0;
@@ -147,15 +276,12 @@ fn slide_array(mut m: [u8; 4]) -> Option<SlideLine> {
 
         // exit if rest are zeros
         if m[i] == 0 {
-            return return_if_moved(moved, m, score);
+            break;
         }
 
-        for k in i + 1..4 {
+        for k in i + 1..N {
             if m[i] == m[k] {
                 m[i] += 1;
-                if m[i] > 15 {
-                    return None;
-                }
                 m[k] = 0;
                 moved = true;
                 // add score
@@ -168,16 +294,8 @@ fn slide_array(mut m: [u8; 4]) -> Option<SlideLine> {
         }
     }
 
-    return_if_moved(moved, m, score)
-}
-
-/// don't store non changed moves
-fn return_if_moved(moved: bool, m: [u8; 4], score: u32) -> Option<SlideLine> {
     if moved {
-        Some(SlideLine {
-            line: matrix::to_u16(m),
-            score: if score == 0 { 0 } else { (score - 1) as u16 },
-        })
+        Some(score)
     } else {
         None
     }
@@ -195,7 +313,7 @@ mod tests {
                 assert_eq!(sl.line, matrix::to_u16([2, 0, 0, 0]));
                 assert_eq!(sl.score, 4 - 1);
             }
-            None => assert!(false),
+            None => panic!("expected moved line"),
         }
     }
 
@@ -208,7 +326,7 @@ mod tests {
                 assert_eq!(sl.line, matrix::to_u16([2, 2, 0, 0]));
                 assert_eq!(sl.score, 8 - 1);
             }
-            None => assert!(false),
+            None => panic!("expected moved line"),
         }
     }
 
@@ -221,7 +339,7 @@ mod tests {
                 assert_eq!(sl.line, matrix::to_u16([3, 2, 0, 0]));
                 assert_eq!(sl.score, 12 - 1);
             }
-            None => assert!(false),
+            None => panic!("expected moved line"),
         }
     }
 
@@ -229,12 +347,9 @@ mod tests {
     fn slide_test4() {
         let a = [2, 0, 0, 0];
         let actual = slide_array(a);
-        match actual {
-            Some(sl) => {
-                assert_eq!(sl.line, matrix::to_u16([2, 0, 0, 0]));
-                assert_eq!(sl.score, 0);
-            }
-            None => assert!(true),
+        if let Some(sl) = actual {
+            assert_eq!(sl.line, matrix::to_u16([2, 0, 0, 0]));
+            assert_eq!(sl.score, 0);
         }
     }
 
@@ -247,38 +362,115 @@ mod tests {
                 assert_eq!(sl.line, matrix::to_u16([2, 0, 0, 0]));
                 assert_eq!(sl.score, 0);
             }
-            None => assert!(false),
+            None => panic!("expected moved line"),
         }
     }
 
     #[test]
-    //#[ignore]
+    fn slide_row_any_size() {
+        let mut a = [1, 0, 1];
+        assert_eq!(slide_row(&mut a), Some(4));
+        assert_eq!(a, [2, 0, 0]);
+
+        let mut a = [0, 3, 3, 3, 3, 1];
+        assert_eq!(slide_row(&mut a), Some(32));
+        assert_eq!(a, [4, 4, 1, 0, 0, 0]);
+
+        let mut a = [2, 1, 0, 0, 0];
+        assert_eq!(slide_row(&mut a), None);
+    }
+
+    #[test]
     fn serialize_bytes() {
-        SlideCache::create_cache();
+        let expected = SlideCache::generate();
+        let bytes = expected.serialize();
+        assert_eq!(
+            bytes.len(),
+            HEADER_SIZE + ACTUAL_SIZE * RECORD_SIZE + CHECKSUM_SIZE
+        );
+
+        let actual = SlideCache::deserialize(&bytes).unwrap();
+        for (a, b) in actual.table.iter().zip(expected.table.iter()) {
+            assert_eq!((a.line, a.score), (b.line, b.score));
+        }
+    }
+
+    #[test]
+    fn serialize_file() {
+        let path = std::env::temp_dir().join("slide-cache-serialize-test.bin");
+        SlideCache::create_cache(&path).unwrap();
+        let actual = SlideCache::load_cache(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(actual.unwrap().table[0x0101].line, 0x2000);
+
+        let missing = std::env::temp_dir().join("slide-cache-missing-test.bin");
+        assert!(matches!(
+            SlideCache::load_cache(&missing),
+            Err(CacheError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn deserialize_bad_files() {
+        let bytes = SlideCache::generate().serialize();
+
+        let truncated = &bytes[..bytes.len() - 1];
+        assert!(matches!(
+            SlideCache::deserialize(truncated),
+            Err(CacheError::Truncated)
+        ));
+
+        let mut magic = bytes.clone();
+        magic[0] = b'x';
+        assert!(matches!(
+            SlideCache::deserialize(&magic),
+            Err(CacheError::BadMagic)
+        ));
+
+        let mut version = bytes.clone();
+        version[5] = 2;
+        assert!(matches!(
+            SlideCache::deserialize(&version),
+            Err(CacheError::UnsupportedVersion(2))
+        ));
+
+        let mut size = bytes.clone();
+        size[6] = 5;
+        assert!(matches!(
+            SlideCache::deserialize(&size),
+            Err(CacheError::Incompatible {
+                row_size: 5,
+                max_exp: 15
+            })
+        ));
+
+        let mut garbage = bytes;
+        garbage[HEADER_SIZE + 1] ^= 1;
+        assert!(matches!(
+            SlideCache::deserialize(&garbage),
+            Err(CacheError::Checksum { .. })
+        ));
     }
 
     #[test]
-    //#[ignore]
-    fn deserialize_bytes() {
-        let cache = SlideCache::load_cache();
+    fn generate_bytes() {
+        let cache = SlideCache::generate();
         let actual = cache.table[matrix::to_u16([2, 2, 1, 1]) as usize];
         assert_eq!(actual.line, matrix::to_u16([3, 2, 0, 0]));
         assert_eq!(actual.score, 12 - 1);
     }
 
     #[test]
-    //#[ignore]
-    fn deserialize_bytes2() {
-        let cache = SlideCache::load_cache();
+    fn generate_bytes2() {
+        let cache = SlideCache::generate();
         let actual = cache.table[matrix::to_u16([0, 2, 1, 2]) as usize];
         assert_eq!(actual.line, matrix::to_u16([2, 1, 2, 0]));
         assert_eq!(actual.score, 0);
     }
 
     #[test]
-    //#[ignore]
-    fn deserialize_size_bytes() {
-        let cache = SlideCache::load_cache();
+    fn generate_size_bytes() {
+        let cache = SlideCache::generate();
         let actual = cache.table.iter().filter(|x| x.line != 0).count();
         assert_eq!(actual, ACTUAL_SIZE);
     }
@@ -28,6 +28,14 @@ pub enum Direction {
 }
 
 impl Direction {
+    /// Every direction in the order moves are tried
+    pub(crate) const ALL: [Direction; 4] = [
+        Direction::Left,
+        Direction::Right,
+        Direction::Down,
+        Direction::Up,
+    ];
+
     pub(super) fn get_mask(self) -> (usize, usize) {
         match self {
             Direction::Right => (1usize, 0usize),
@@ -47,6 +55,18 @@ impl Direction {
     // Down      = ↑ → ↥
 }
 
+/// Key of the direction, inverse of 'try_from'
+impl From<Direction> for char {
+    fn from(dir: Direction) -> Self {
+        match dir {
+            Direction::Up => 'w',
+            Direction::Left => 'a',
+            Direction::Down => 's',
+            Direction::Right => 'd',
+        }
+    }
+}
+
 impl TryFrom<char> for Direction {
     type Error = ();
     fn try_from(ch: char) -> Result<Self, Self::Error> {
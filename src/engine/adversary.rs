@@ -0,0 +1,138 @@
+/* adversary.rs -- the worst random moves.
+Copyright (C) 2021 fuggy
+
+This file is part of game-2048-engine.
+
+game-2048-engine is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+game-2048-engine is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with game-2048-engine.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::board::Board;
+use crate::engine::engine_config::{Algorithm, EngineConfig};
+use crate::engine::evaluation::Weights;
+use crate::engine::moves::Move;
+use crate::engine::node::Node;
+use crate::engine::search::Search;
+use crate::engine::transposition::TranspositionTable;
+use crate::random::SpawnRules;
+
+/// Spawner choosing the tile as bad as possible for the player,
+/// the min player of minimax
+#[derive(Clone, Copy)]
+pub struct EvilSpawner {
+    pub eval_fn: Weights,
+    /// Moves searched after the spawn, 0 - evaluation of the spawn itself
+    pub plies: u16,
+}
+
+impl EvilSpawner {
+    /// The spawn with the worst position for the player 'plies' moves later,
+    /// later spawns are searched by the same rules
+    /// returns: (value, position in empty cell), none without values or empty cells
+    pub fn choose<const N: usize>(&self, board: &Board<N>, rules: &SpawnRules) -> Option<(u8, u8)> {
+        let config = EngineConfig {
+            spawn_rules: rules.clone(),
+            ..EngineConfig::new(self.plies, self.eval_fn, Algorithm::MinimaxAlphaBeta)
+        };
+        let mut table = TranspositionTable::new(config.transposition_size);
+        let mut search = Search::new(&config, &mut table);
+
+        let mut worst = None;
+        let mut beta = i32::MAX;
+        for value in rules.values.exponents() {
+            for pos in 0..board.empty_count() {
+                let mut next = *board;
+                next.set_move((value, pos));
+                let mut node = Node::with_board(next, Move::Random(value, pos));
+                //fail soft, not better spawns return at least beta
+                let score = node
+                    .minimax_alphabeta(&mut search, self.plies, i32::MIN + 1, beta, true)
+                    .score;
+                if score < beta || worst.is_none() {
+                    beta = score;
+                    worst = Some((value, pos));
+                }
+            }
+        }
+        worst
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board;
+    use crate::random::SpawnValues;
+
+    fn weights() -> Weights {
+        Weights {
+            max_cell: 30,
+            max_score: 10,
+            monotonicity: 100,
+            smoothness: 50,
+            std_dev: 0,
+            free_space: 300,
+            snakeiness: 0,
+        }
+        .normalize()
+    }
+
+    fn rules(values: &[u8]) -> SpawnRules {
+        SpawnRules {
+            values: SpawnValues::Weighted(values.iter().map(|&exp| (exp, 1)).collect()),
+            ..SpawnRules::default()
+        }
+    }
+
+    #[test]
+    fn should_spawn_losing_tile() {
+        board::load_cache();
+        let board: Board = "2 4 2 4\n4 2 4 2\n2 4 2 4\n4 2 4 .".parse().unwrap();
+        let evil = EvilSpawner {
+            eval_fn: weights(),
+            plies: 1,
+        };
+        assert_eq!(evil.choose(&board, &rules(&[1, 2])), Some((1, 0)));
+        assert_eq!(evil.choose(&board, &rules(&[2])), Some((2, 0)));
+    }
+
+    #[test]
+    fn should_not_spawn_without_values() {
+        let board: Board = "2 4 2 4\n4 2 4 2\n2 4 2 4\n4 2 4 .".parse().unwrap();
+        let evil = EvilSpawner {
+            eval_fn: weights(),
+            plies: 1,
+        };
+        assert_eq!(evil.choose(&board, &rules(&[])), None);
+
+        let full: Board = "2 4 2 4\n4 2 4 2\n2 4 2 4\n4 2 4 2".parse().unwrap();
+        assert_eq!(evil.choose(&full, &rules(&[1, 2])), None);
+    }
+
+    #[test]
+    fn should_spawn_away_from_merge() {
+        board::load_cache();
+        let board: Board = "2 . . .\n. . . .\n. . . .\n. . . .".parse().unwrap();
+        let evil = EvilSpawner {
+            eval_fn: weights(),
+            plies: 0,
+        };
+        //the same tile next to it is merged by the next move
+        let (value, pos) = evil.choose(&board, &rules(&[1, 2])).unwrap();
+        let mut next = board;
+        next.set_move((value, pos));
+        let tiles = next.tiles();
+        assert_ne!(tiles[0][1], 2);
+        assert_ne!(tiles[1][0], 2);
+    }
+}
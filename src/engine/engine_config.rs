@@ -17,14 +17,78 @@ You should have received a copy of the GNU General Public License
 along with game-2048-engine.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::board::Board;
 use crate::engine::evaluation::Weights;
+use crate::random::SpawnRules;
+use std::time::Duration;
 
 pub struct EngineConfig {
+    /// Search depth in plies, maximum for adaptive depth
     pub depth: u16,
+    pub depth_policy: DepthPolicy,
     pub eval_fn: Weights,
     pub algorithm: Algorithm,
     pub random_mode: RandomCompleteness,
     pub order_moves: bool,
+    /// Transposition table entries, 0 - disabled
+    pub transposition_size: usize,
+    /// Worker threads searching root children, 1 - single thread
+    pub threads: usize,
+    /// Chance node branches less likely than this are evaluated statically, 0 - disabled
+    pub probability_cutoff: f64,
+    /// Reaching this tile ends the search as won, None - no goal except not losing
+    pub target: Option<u64>,
+    /// Random moves of the searched game, 'Engine::from_game' takes the rules of the game
+    pub spawn_rules: SpawnRules,
+}
+
+impl EngineConfig {
+    /// Full search of fixed depth with move ordering, other options are disabled
+    pub fn new(depth: u16, eval_fn: Weights, algorithm: Algorithm) -> Self {
+        EngineConfig {
+            depth,
+            depth_policy: DepthPolicy::Fixed,
+            eval_fn,
+            algorithm,
+            random_mode: RandomCompleteness::Full,
+            order_moves: true,
+            transposition_size: 0,
+            threads: 1,
+            probability_cutoff: 0.0,
+            target: None,
+            spawn_rules: SpawnRules::default(),
+        }
+    }
+}
+
+pub enum DepthPolicy {
+    /// Always 'depth' plies
+    Fixed,
+    /// From 'min_depth' in open positions up to 'depth' on crowded boards
+    Adaptive { min_depth: u16 },
+}
+
+/// More different tiles than in opening need deeper search
+const OPEN_DISTINCT_COUNT: u8 = 4;
+/// Less empty cells is close to losing
+const CROWDED_EMPTY_COUNT: u8 = 4;
+
+impl DepthPolicy {
+    /// Search depth for the position
+    pub fn depth<const N: usize>(&self, max_depth: u16, board: &Board<N>) -> u16 {
+        match *self {
+            DepthPolicy::Fixed => max_depth,
+            DepthPolicy::Adaptive { min_depth } => {
+                let mut depth = min_depth;
+                depth += board.distinct_count().saturating_sub(OPEN_DISTINCT_COUNT) as u16;
+                if board.empty_count() < CROWDED_EMPTY_COUNT {
+                    //one more move of each player
+                    depth += 2;
+                }
+                depth.clamp(min_depth, max_depth.max(min_depth))
+            }
+        }
+    }
 }
 
 pub enum RandomCompleteness {
@@ -36,6 +100,7 @@ pub enum RandomCompleteness {
     MonteCarlo(u8),
 }
 
+#[derive(Clone, Copy)]
 pub enum Algorithm {
     Minimax,
     MinimaxAlphaBeta,
@@ -43,4 +108,91 @@ pub enum Algorithm {
     NegamaxAlphaBeta,
     NegaScout,
     ExpectiMinimax,
+    /// Expectimax with Star1/Star2 pruning of chance nodes
+    ExpectiMinimaxStar2,
+    /// Monte Carlo tree search, doesn't use depth and weights
+    Mcts(Budget),
+    /// Random games from every possible move, doesn't use depth and weights
+    RandomRollout(Rollout),
+}
+
+/// Limit of Monte Carlo tree search per move
+#[derive(Clone, Copy)]
+pub enum Budget {
+    /// Number of playouts
+    Iterations(u32),
+    /// Time per move
+    Time(Duration),
+}
+
+#[derive(Clone, Copy)]
+pub struct Rollout {
+    /// Games per move
+    pub count: u32,
+    pub policy: RolloutPolicy,
+    pub target: RolloutTarget,
+}
+
+/// Choice of moves in random games
+#[derive(Clone, Copy)]
+pub enum RolloutPolicy {
+    /// Any possible move with equal chance
+    Uniform,
+    /// First possible of down, left, right, up
+    Corner,
+}
+
+/// Compared average result of random games
+#[derive(Clone, Copy)]
+pub enum RolloutTarget {
+    Score,
+    MaxCell,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADAPTIVE: DepthPolicy = DepthPolicy::Adaptive { min_depth: 3 };
+
+    #[test]
+    #[rustfmt::skip]
+    fn should_search_opening_shallow() {
+        let mut board = Board::new();
+        board.set_cells(
+           [[1, 0, 0, 0],
+            [0, 2, 0, 0],
+            [0, 0, 0, 0],
+            [0, 1, 0, 0]]);
+
+        assert_eq!(ADAPTIVE.depth(9, &board), 3);
+        assert_eq!(DepthPolicy::Fixed.depth(9, &board), 9);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn should_search_middle_game_deeper() {
+        let mut board = Board::new();
+        board.set_cells(
+           [[1, 2, 3, 0],
+            [0, 4, 5, 6],
+            [2, 0, 1, 0],
+            [0, 0, 0, 1]]);
+
+        assert_eq!(ADAPTIVE.depth(9, &board), 5);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn should_search_crowded_board_to_max() {
+        let mut board = Board::new();
+        board.set_cells(
+           [[1, 2, 3, 4],
+            [5, 6, 7, 8],
+            [9, 10, 1, 2],
+            [0, 0, 0, 1]]);
+
+        assert_eq!(ADAPTIVE.depth(9, &board), 9);
+        assert_eq!(ADAPTIVE.depth(2, &board), 3);
+    }
 }
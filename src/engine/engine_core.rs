@@ -17,48 +17,151 @@ You should have received a copy of the GNU General Public License
 along with game-2048-engine.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::board::BOARD_SIZE;
 use crate::direction::Direction;
 use crate::engine::engine_config::Algorithm;
+use crate::engine::engine_config::Budget;
 use crate::engine::engine_config::EngineConfig;
-use crate::engine::moves::Move;
+use crate::engine::engine_config::Rollout;
+use crate::engine::mcts;
+use crate::engine::moves::{BestMove, Move};
 use crate::engine::node::Node;
+use crate::engine::rollout;
+use crate::engine::search::Search;
+use crate::engine::transposition::TranspositionTable;
 use crate::game::Game;
+use std::cmp::Ordering;
+use std::time::{Duration, Instant};
 
-pub struct Engine {
-    pub(super) root: Node,
+pub struct Engine<const N: usize = BOARD_SIZE> {
+    pub(super) root: Node<N>,
     pub(super) config: EngineConfig,
+    /// Depth of the last completed search
+    depth: u16,
+    /// Transposition table per worker thread
+    tables: Vec<TranspositionTable>,
+    visits: Vec<(Direction, u32)>,
+    averages: Vec<(Direction, f64)>,
 }
 
-impl Engine {
-    pub fn from_game(game: &Game, config: EngineConfig) -> Self {
+impl<const N: usize> Engine<N> {
+    /// Engine searching random moves by spawn rules of the game
+    pub fn from_game(game: &Game<N>, mut config: EngineConfig) -> Self {
+        config.spawn_rules = game.rules().clone();
         Engine {
             root: Node::with_board(game.board, Move::default()),
+            tables: new_tables(&config),
             config,
+            depth: 0,
+            visits: Vec::new(),
+            averages: Vec::new(),
         }
     }
 
     pub fn best_move(&mut self) -> Direction {
-        let best_move = match self.config.algorithm {
-            Algorithm::Minimax => self.root.minimax(&self.config, self.config.depth, true),
-            Algorithm::MinimaxAlphaBeta => self.root.minimax_alphabeta(
-                &self.config,
-                self.config.depth,
-                i32::min_value() + 1,
-                i32::max_value(),
-                true,
-            ),
-            Algorithm::Negamax => self.root.negamax(&self.config, self.config.depth, 1),
-            Algorithm::NegamaxAlphaBeta => self.root.negamax_alphabeta(
-                &self.config,
-                self.config.depth,
-                i32::min_value() + 1,
-                i32::max_value(),
-                1,
-            ),
-            Algorithm::NegaScout => unimplemented!(),
-            Algorithm::ExpectiMinimax => unimplemented!(),
+        match self.config.algorithm {
+            Algorithm::Mcts(budget) => return self.best_mcts_move(budget),
+            Algorithm::RandomRollout(rollout) => return self.best_rollout_move(rollout),
+            _ => {}
+        }
+
+        let depth = self.search_depth();
+        let mut searches = new_searches(&self.config, &mut self.tables, None);
+        let best_move = search_root(&mut self.root, &mut searches, depth);
+        self.depth = depth;
+        self.play(best_move)
+    }
+
+    /// Depth of the root position by depth policy
+    fn search_depth(&self) -> u16 {
+        self.config
+            .depth_policy
+            .depth(self.config.depth, &self.root.board)
+    }
+
+    /// Iterative deepening up to 'depth' within time budget.
+    /// The best move of the last completed iteration is played.
+    /// Random rollouts play a fixed number of games and ignore the budget
+    pub fn best_move_within(&mut self, budget: Duration) -> Direction {
+        match self.config.algorithm {
+            Algorithm::Mcts(_) => return self.best_mcts_move(Budget::Time(budget)),
+            Algorithm::RandomRollout(rollout) => return self.best_rollout_move(rollout),
+            _ => {}
+        }
+
+        let deadline = Instant::now() + budget;
+        // the first iteration is always completed
+        let mut searches = new_searches(&self.config, &mut self.tables, None);
+        let mut best_move = search_root(&mut self.root, &mut searches, 1);
+        let mut depth = 1;
+        let max_depth = self.search_depth();
+
+        while depth < max_depth && Instant::now() < deadline {
+            let mut searches = new_searches(&self.config, &mut self.tables, Some(deadline));
+            let next_move = search_root(&mut self.root, &mut searches, depth + 1);
+            if searches.iter().any(Search::is_aborted) {
+                break;
+            }
+            best_move = next_move;
+            depth += 1;
+        }
+
+        self.depth = depth;
+        self.play(best_move)
+    }
+
+    /// Depth of the last completed search, the last iteration within time budget
+    pub fn completed_depth(&self) -> u16 {
+        self.depth
+    }
+
+    /// Monte Carlo tree search, the most visited move is played
+    fn best_mcts_move(&mut self, budget: Budget) -> Direction {
+        self.visits = mcts::visit_counts(self.root.board, &self.config.spawn_rules, budget);
+
+        let dir = match self.visits.iter().max_by_key(|(_dir, count)| *count) {
+            Some(&(dir, _count)) => dir,
+            None => panic!("This is terminal position"),
+        };
+        self.play_without_tree(dir)
+    }
+
+    /// Random games, the move with the best average result is played
+    fn best_rollout_move(&mut self, rollout: Rollout) -> Direction {
+        self.averages =
+            rollout::average_results(self.root.board, &self.config.spawn_rules, rollout);
+
+        let best = self
+            .averages
+            .iter()
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        let dir = match best {
+            Some(&(dir, _avg)) => dir,
+            None => panic!("This is terminal position"),
         };
+        self.play_without_tree(dir)
+    }
+
+    /// Move root for algorithms which don't use node tree
+    fn play_without_tree(&mut self, dir: Direction) -> Direction {
+        let mut board = self.root.board;
+        board.slide_to(dir);
+        self.root = Node::with_board(board, Move::Human(dir));
+        dir
+    }
+
+    /// Root visit counts of the last Monte Carlo tree search
+    pub fn visit_counts(&self) -> &[(Direction, u32)] {
+        &self.visits
+    }
 
+    /// Average results per move of the last random rollouts
+    pub fn rollout_averages(&self) -> &[(Direction, f64)] {
+        &self.averages
+    }
+
+    /// Move root to the best child
+    fn play(&mut self, best_move: BestMove) -> Direction {
         //best_turn
         if let Some(ref mut vec) = self.root.children {
             self.root = vec.swap_remove(best_move.local_id as usize);
@@ -66,9 +169,10 @@ impl Engine {
 
         //TODO
         println!(
-            "stat Statistics {{ total_nodes: {:?}, cut_nodes: {:?}, uniq_nodes: {:?} }}",
+            "stat Statistics {{ total_nodes: {:?}, cut_nodes: {:?}, table_hits: {:?}, uniq_nodes: {:?} }}",
             best_move.stat.total_nodes,
             best_move.stat.cut_nodes,
+            best_move.stat.table_hits,
             best_move.stat.cache_hit.len()
         );
         best_move.stat.print_cache_stat();
@@ -84,3 +188,232 @@ impl Engine {
         self.root = self.root.find_next_random_move(random_move);
     }
 }
+
+/// Split transposition table between worker threads
+fn new_tables(config: &EngineConfig) -> Vec<TranspositionTable> {
+    let threads = config.threads.max(1);
+    (0..threads)
+        .map(|_| TranspositionTable::new(config.transposition_size / threads))
+        .collect()
+}
+
+fn new_searches<'a>(
+    config: &'a EngineConfig,
+    tables: &'a mut [TranspositionTable],
+    deadline: Option<Instant>,
+) -> Vec<Search<'a>> {
+    tables
+        .iter_mut()
+        .map(|table| match deadline {
+            Some(deadline) => Search::with_deadline(config, table, deadline),
+            None => Search::new(config, table),
+        })
+        .collect()
+}
+
+fn search_root<const N: usize>(
+    root: &mut Node<N>,
+    searches: &mut [Search],
+    depth: u16,
+) -> BestMove {
+    let max_cell = root.board.max_cell();
+    for search in searches.iter_mut() {
+        search.root_depth = depth;
+        //game continued after reaching target
+        search.goal = search.config.target.filter(|&target| max_cell < target);
+    }
+    if searches.len() > 1 {
+        root.search_parallel(searches, depth)
+    } else {
+        root.search(&mut searches[0], depth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board;
+    use crate::engine::engine_config::{RolloutPolicy, RolloutTarget};
+    use crate::engine::evaluation::Weights;
+    use crate::random::{SpawnRules, Xoshiro256};
+
+    fn config(depth: u16) -> EngineConfig {
+        let weights = Weights {
+            max_cell: 30,
+            max_score: 10,
+            monotonicity: 100,
+            smoothness: 50,
+            std_dev: 0,
+            free_space: 300,
+            snakeiness: 0,
+        };
+        EngineConfig {
+            transposition_size: 1 << 16,
+            ..EngineConfig::new(depth, weights.normalize(), Algorithm::ExpectiMinimax)
+        }
+    }
+
+    #[test]
+    fn should_follow_game_with_two_spawns_per_turn() {
+        board::load_cache();
+        let rules = SpawnRules {
+            tiles_per_turn: 2,
+            ..SpawnRules::default()
+        };
+        let mut game: Game = Game::with_rules(Xoshiro256::with_seed(3), rules).unwrap();
+        let mut engine = Engine::from_game(&game, config(2));
+
+        for _ in 0..3 {
+            assert!(game.human_move(engine.best_move()));
+            for _ in 0..2 {
+                let (value, pos) = game.random_move().unwrap();
+                engine.make_random_move(Move::Random(value, pos));
+            }
+            assert!(engine.root.board.same_cells(&game.board));
+        }
+    }
+
+    #[test]
+    fn should_play_most_visited_move() {
+        board::load_cache();
+        let game: Game = Game::with_seed(3);
+
+        let mut config = config(1);
+        config.algorithm = Algorithm::Mcts(Budget::Iterations(100));
+        let mut engine = Engine::from_game(&game, config);
+        let actual = engine.best_move();
+
+        let visits = engine.visit_counts();
+        let max_count = visits.iter().map(|&(_dir, count)| count).max();
+        assert!(visits.contains(&(actual, max_count.unwrap())));
+    }
+
+    #[test]
+    fn should_play_best_rollout_move() {
+        board::load_cache();
+        let game: Game = Game::with_seed(3);
+
+        let mut config = config(1);
+        config.algorithm = Algorithm::RandomRollout(Rollout {
+            count: 20,
+            policy: RolloutPolicy::Uniform,
+            target: RolloutTarget::Score,
+        });
+        let mut engine = Engine::from_game(&game, config);
+        let actual = engine.best_move();
+
+        let averages = engine.rollout_averages();
+        let best = averages.iter().map(|&(_dir, avg)| avg).fold(0.0, f64::max);
+        assert!(averages.contains(&(actual, best)));
+
+        let mut next: Game = Game::with_seed(3);
+        assert!(next.human_move(actual));
+        assert_eq!(engine.root.board.get_board_id(), next.board.get_board_id());
+    }
+
+    #[test]
+    fn should_play_rollout_move_within_budget() {
+        board::load_cache();
+        let game: Game = Game::with_seed(3);
+
+        let mut config = config(1);
+        config.algorithm = Algorithm::RandomRollout(Rollout {
+            count: 5,
+            policy: RolloutPolicy::Corner,
+            target: RolloutTarget::Score,
+        });
+        let mut engine = Engine::from_game(&game, config);
+        let actual = engine.best_move_within(Duration::from_millis(10));
+
+        let mut next: Game = Game::with_seed(3);
+        assert!(next.human_move(actual));
+        assert_eq!(engine.root.board.get_board_id(), next.board.get_board_id());
+    }
+
+    #[test]
+    fn should_complete_first_iteration() {
+        board::load_cache();
+        let game: Game = Game::with_seed(3);
+
+        let mut engine = Engine::from_game(&game, config(1));
+        let expected = engine.best_move();
+        let mut engine = Engine::from_game(&game, config(7));
+        let actual = engine.best_move_within(Duration::from_millis(0));
+
+        assert_eq!(actual, expected);
+        assert_eq!(engine.completed_depth(), 1);
+    }
+
+    #[test]
+    fn should_play_same_move_in_parallel() {
+        board::load_cache();
+        let game: Game = Game::with_seed(3);
+
+        let mut engine = Engine::from_game(&game, config(3));
+        let expected = engine.best_move();
+        let mut config = config(3);
+        config.threads = 4;
+        let mut engine = Engine::from_game(&game, config);
+        let actual = engine.best_move();
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn should_stop_at_max_depth() {
+        board::load_cache();
+        let game: Game = Game::with_seed(3);
+
+        let mut engine = Engine::from_game(&game, config(3));
+        let expected = engine.best_move();
+        let mut engine = Engine::from_game(&game, config(3));
+        let actual = engine.best_move_within(Duration::from_secs(60));
+
+        assert_eq!(actual, expected);
+        assert_eq!(engine.completed_depth(), 3);
+    }
+
+    #[test]
+    fn should_search_other_sizes() {
+        let small: Game<3> = Game::with_seed(3);
+        let mut engine = Engine::from_game(&small, config(3));
+        engine.best_move();
+
+        let big: Game<5> = Game::with_seed(3);
+        let mut engine = Engine::from_game(&big, config(3));
+        engine.best_move();
+        assert_eq!(engine.root.board.move_count, 1);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn should_reach_target_first() {
+        board::load_cache();
+        let mut game: Game = Game::with_seed(3);
+        game.board.set_cells(
+           [[5, 0, 0, 0],
+            [5, 0, 0, 0],
+            [0, 0, 0, 0],
+            [0, 0, 0, 0]]);
+
+        let mut config = config(3);
+        config.eval_fn = Weights {
+            max_cell: 0,
+            max_score: 0,
+            monotonicity: 1,
+            smoothness: 0,
+            std_dev: 0,
+            free_space: 0,
+            snakeiness: 0,
+        };
+        config.target = Some(64);
+        let mut engine = Engine::from_game(&game, config);
+        let actual = engine.best_move();
+        assert!(actual == Direction::Up || actual == Direction::Down);
+        assert_eq!(engine.root.board.max_cell(), 64);
+
+        //target is already reached, search as usual
+        engine.make_random_move(Move::Random(1, 0));
+        engine.best_move();
+    }
+}
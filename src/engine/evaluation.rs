@@ -48,14 +48,50 @@ impl Weights {
         self.snakeiness *= 23;
         self
     }
+
+    /// Symmetric boards get the same evaluation, only snake has a fixed corner
+    pub fn is_symmetric(&self) -> bool {
+        self.snakeiness == 0
+    }
 }
 
-pub(super) fn evaluate(weights: Weights, node: &Node) -> i32 {
-    // TODO in ordered moves, after replace with cache
-    if node.value != 0 {
-        return node.value;
+/// Range of the evaluation function from ranges of components,
+/// theoretical with the biggest possible tile not effective ones.
+/// Big weights make it wider than i32, it saturates only at i64
+pub(super) fn bounds<const N: usize>(weights: Weights) -> (i64, i64) {
+    let cells = (N * N) as i128;
+    //the biggest exponent, every cell is merged into one tile of u64
+    let exp = (cells + 1).min(63);
+    let pairs = 2 * N as i128 * (N as i128 - 1);
+    let snake: i128 = (0..N)
+        .flat_map(|j| (0..N).map(move |i| matrix::snake_coefficient::<N>(j, i) as i128))
+        .sum();
+    let components = [
+        (weights.max_cell, 0, 1 << exp),
+        //each tile 2^k scores at most (k - 1) * 2^k
+        (weights.max_score, 0, (exp - 1) << (exp + 1)),
+        (weights.monotonicity, 0, 2 * N as i128),
+        (weights.smoothness, 384 - pairs * exp, 384),
+        (weights.std_dev, 1000 - N as i128 * exp * 29000 / 2, 1000),
+        (weights.free_space, 0, cells),
+        (weights.snakeiness, 0, N as i128 * exp * snake),
+    ];
+
+    let mut low = 0i128;
+    let mut high = 0i128;
+    for &(weight, min, max) in &components {
+        //negative weight swaps the range
+        let (a, b) = (weight as i128 * min, weight as i128 * max);
+        low += a.min(b);
+        high += a.max(b);
     }
+    let saturate = |x: i128| x.clamp(i64::MIN as i128, i64::MAX as i128) as i64;
+    (saturate(low), saturate(high))
+}
 
+pub(super) fn evaluate<const N: usize>(weights: Weights, node: &Node<N>) -> i32 {
+    //unpacked once for heuristics over rows and columns
+    let cells = node.board.cells();
     let mut score = 0;
     if weights.max_cell != 0 {
         score += weights.max_cell * evaluation_max_cell(node);
@@ -64,56 +100,84 @@ pub(super) fn evaluate(weights: Weights, node: &Node) -> i32 {
         score += weights.max_score * evaluation_max_score(node);
     }
     if weights.monotonicity != 0 {
-        score += weights.monotonicity * evaluation_monotonicity(node);
+        score += weights.monotonicity * evaluation_monotonicity(&cells);
     }
     if weights.smoothness != 0 {
-        score += weights.smoothness * evaluation_smoothness(node);
+        score += weights.smoothness * evaluation_smoothness(&cells);
     }
     if weights.std_dev != 0 {
-        score += weights.std_dev * evaluation_std_dev(node);
+        score += weights.std_dev * evaluation_std_dev(&cells);
     }
     if weights.free_space != 0 {
         score += weights.free_space * evaluation_free_space(node);
     }
     if weights.snakeiness != 0 {
-        score += weights.snakeiness * evaluation_snakeiness(node);
+        score += weights.snakeiness * evaluation_snakeiness(&cells);
     }
     score
 }
 
 /// range 0..65536 theory max 131072
-fn evaluation_max_cell(node: &Node) -> i32 {
+fn evaluation_max_cell<const N: usize>(node: &Node<N>) -> i32 {
     node.board.max_cell() as i32
 }
 
 /// range 0..~900_000 effective to win 60_000
-fn evaluation_max_score(node: &Node) -> i32 {
+fn evaluation_max_score<const N: usize>(node: &Node<N>) -> i32 {
     node.board.score as i32
 }
 
 /// range 0..8
-fn evaluation_monotonicity(node: &Node) -> i32 {
-    matrix::monotonicity(&node.board.board)
+fn evaluation_monotonicity<const N: usize>(cells: &[[u8; N]; N]) -> i32 {
+    matrix::monotonicity(cells)
 }
 
 // range 0..384
-fn evaluation_smoothness(node: &Node) -> i32 {
+fn evaluation_smoothness<const N: usize>(cells: &[[u8; N]; N]) -> i32 {
     //negate this - less is better
-    -matrix::smoothness(&node.board.board) + 384
+    -matrix::smoothness(cells) + 384
 }
 
 // range 0..~912_000
-fn evaluation_std_dev(node: &Node) -> i32 {
+fn evaluation_std_dev<const N: usize>(cells: &[[u8; N]; N]) -> i32 {
     //negate this - less is better
-    -matrix::std_dev(&node.board.board) + 1000
+    -matrix::std_dev(cells) + 1000
 }
 
 // range 0..15
-fn evaluation_free_space(node: &Node) -> i32 {
-    matrix::empty_count(&node.board.board) as i32
+fn evaluation_free_space<const N: usize>(node: &Node<N>) -> i32 {
+    node.board.empty_count() as i32
 }
 
 // range 0..39312
-fn evaluation_snakeiness(node: &Node) -> i32 {
-    matrix::snakeiness(&node.board.board) as i32
+fn evaluation_snakeiness<const N: usize>(cells: &[[u8; N]; N]) -> i32 {
+    matrix::snakeiness(cells)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_keep_bounds_wider_than_i32() {
+        let weights = Weights {
+            max_cell: 0,
+            max_score: 1000,
+            monotonicity: 0,
+            smoothness: 0,
+            std_dev: 0,
+            free_space: 0,
+            snakeiness: 0,
+        };
+        //131072 is the biggest tile, 16 merges of 2^17 score 16 * 2^18
+        assert_eq!(bounds::<4>(weights), (0, 1000 * (16 << 18)));
+
+        let weights = Weights {
+            max_score: i32::MIN,
+            snakeiness: i32::MIN,
+            ..weights
+        };
+        let (low, high) = bounds::<16>(weights);
+        assert_eq!((low, high), (i64::MIN, 0));
+    }
 }
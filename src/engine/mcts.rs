@@ -0,0 +1,236 @@
+/* mcts.rs -- monte carlo tree search.
+Copyright (C) 2021 fuggy
+
+This file is part of game-2048-engine.
+
+game-2048-engine is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+game-2048-engine is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with game-2048-engine.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::board::Board;
+use crate::direction::Direction;
+use crate::engine::engine_config::{Budget, RolloutPolicy};
+use crate::engine::moves::Move;
+use crate::engine::rollout;
+use crate::game::Game;
+use crate::random;
+use crate::random::{RndMove, SpawnRules, Xoshiro256};
+use std::time::Instant;
+
+/// Exploration constant of UCT
+const EXPLORATION: f64 = std::f64::consts::SQRT_2;
+
+struct MctsNode<const N: usize> {
+    board: Board<N>,
+    turn: Move,
+    visits: u32,
+    /// sum of playout scores
+    reward: f64,
+    expanded: bool,
+    children: Vec<MctsNode<N>>,
+}
+
+/// Search from the position, returns visit count of every possible move
+pub(super) fn visit_counts<const N: usize>(
+    board: Board<N>,
+    rules: &SpawnRules,
+    budget: Budget,
+) -> Vec<(Direction, u32)> {
+    let mut root = MctsNode::new(board, Move::default());
+    //every possible move is listed even if the budget ends before visiting it
+    root.expand();
+    let mut rnd = random::get_rnd();
+
+    match budget {
+        Budget::Iterations(count) => {
+            for _ in 0..count {
+                root.iterate(rules, &mut rnd);
+            }
+        }
+        Budget::Time(time) => {
+            let deadline = Instant::now() + time;
+            // at least one playout
+            root.iterate(rules, &mut rnd);
+            while Instant::now() < deadline {
+                root.iterate(rules, &mut rnd);
+            }
+        }
+    }
+
+    root.children
+        .iter()
+        .filter_map(|node| match node.turn {
+            Move::Human(dir) => Some((dir, node.visits)),
+            Move::Random(_, _) => None,
+        })
+        .collect()
+}
+
+impl<const N: usize> MctsNode<N> {
+    fn new(board: Board<N>, turn: Move) -> Self {
+        MctsNode {
+            board,
+            turn,
+            visits: 0,
+            reward: 0.0,
+            expanded: false,
+            children: Vec::new(),
+        }
+    }
+
+    /// One playout through the tree, returns its score
+    fn iterate(&mut self, rules: &SpawnRules, rnd: &mut Xoshiro256) -> f64 {
+        let reward = if self.turn.is_human() {
+            self.sample_random_move(rules, rnd)
+        } else {
+            self.select_human_move(rules, rnd)
+        };
+        self.visits += 1;
+        self.reward += reward;
+        reward
+    }
+
+    /// Chance node, spawns of the turn are chosen the same way as in the game
+    fn sample_random_move(&mut self, rules: &SpawnRules, rnd: &mut Xoshiro256) -> f64 {
+        let mut board = self.board;
+        let mut turn = Move::default();
+        for _ in 0..rules.tiles_per_turn {
+            let empty_count = board.empty_count();
+            if empty_count == 0 {
+                break;
+            }
+            let next_move = rules.next_move(rnd, empty_count);
+            board.set_move(next_move);
+            turn = Move::from_tuple(next_move);
+        }
+        let found = self
+            .children
+            .iter_mut()
+            .find(|node| node.board.same_cells(&board));
+        if let Some(node) = found {
+            return node.iterate(rules, rnd);
+        }
+
+        //new leaf, estimate with random game
+        let mut node = MctsNode::new(board, turn);
+        let seed = rnd.next_u64();
+        let mut game = Game::from_board(board, rules.clone(), Xoshiro256::with_seed(seed));
+        rollout::play_out(&mut game, RolloutPolicy::Uniform, rnd);
+        let reward = game.board.score as f64;
+        node.visits = 1;
+        node.reward = reward;
+        self.children.push(node);
+        reward
+    }
+
+    /// Decision node, unvisited moves first then upper confidence bound
+    fn select_human_move(&mut self, rules: &SpawnRules, rnd: &mut Xoshiro256) -> f64 {
+        if !self.expanded {
+            self.expand();
+        }
+        if self.children.is_empty() {
+            //lost
+            return self.board.score as f64;
+        }
+
+        let index = match self.children.iter().position(|node| node.visits == 0) {
+            Some(index) => index,
+            None => self.best_uct_index(),
+        };
+        self.children[index].iterate(rules, rnd)
+    }
+
+    fn expand(&mut self) {
+        for dir in &Direction::ALL {
+            let mut board = self.board;
+            if board.slide_to(*dir) {
+                self.children.push(MctsNode::new(board, Move::Human(*dir)));
+            }
+        }
+        self.expanded = true;
+    }
+
+    fn best_uct_index(&self) -> usize {
+        // scores are unbounded, scale them by the average of the node
+        let scale = (self.reward / self.visits as f64).max(1.0);
+        let log_visits = (self.visits as f64).ln();
+        let uct = |node: &MctsNode<N>| {
+            let visits = node.visits as f64;
+            node.reward / visits / scale + EXPLORATION * (log_visits / visits).sqrt()
+        };
+
+        let mut best = 0;
+        for (index, node) in self.children.iter().enumerate() {
+            if uct(node) > uct(&self.children[best]) {
+                best = index;
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board;
+    use std::time::Duration;
+
+    #[test]
+    #[rustfmt::skip]
+    fn should_visit_every_move() {
+        board::load_cache();
+        let mut board = Board::new();
+        board.set_cells(
+           [[1, 2, 3, 0],
+            [0, 1, 4, 2],
+            [2, 0, 1, 0],
+            [0, 0, 0, 1]]);
+
+        let visits = visit_counts(board, &SpawnRules::default(), Budget::Iterations(200));
+        assert_eq!(visits.len(), 4);
+        assert!(visits.iter().all(|&(_dir, count)| count > 0));
+        assert_eq!(visits.iter().map(|&(_dir, count)| count).sum::<u32>(), 200);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn should_list_moves_without_iterations() {
+        board::load_cache();
+        let mut board = Board::new();
+        board.set_cells(
+           [[1, 2, 1, 2],
+            [2, 1, 2, 1],
+            [1, 2, 1, 2],
+            [2, 1, 2, 0]]);
+
+        let visits = visit_counts(board, &SpawnRules::default(), Budget::Iterations(0));
+        assert_eq!(visits, vec![(Direction::Right, 0), (Direction::Down, 0)]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn should_skip_impossible_moves() {
+        board::load_cache();
+        let mut board = Board::new();
+        board.set_cells(
+           [[1, 2, 1, 2],
+            [2, 1, 2, 1],
+            [1, 2, 1, 2],
+            [2, 1, 2, 0]]);
+
+        let visits = visit_counts(board, &SpawnRules::default(), Budget::Time(Duration::from_millis(0)));
+        let dirs: Vec<Direction> = visits.iter().map(|&(dir, _count)| dir).collect();
+        assert_eq!(dirs, vec![Direction::Right, Direction::Down]);
+        assert_eq!(visits.iter().map(|&(_dir, count)| count).sum::<u32>(), 1);
+    }
+}
@@ -17,8 +17,13 @@ You should have received a copy of the GNU General Public License
 along with game-2048-engine.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+pub mod adversary;
 pub mod engine_config;
 pub mod engine_core;
 pub mod evaluation;
+mod mcts;
 pub mod moves;
 mod node;
+mod rollout;
+mod search;
+mod transposition;
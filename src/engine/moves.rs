@@ -88,6 +88,8 @@ impl Neg for BestMove {
 pub struct Statistics {
     pub total_nodes: u32,
     pub cut_nodes: u32,
+    pub table_hits: u32,
+    /// evaluations of every canonical board id
     pub cache_hit: HashMap<u64, u32>,
 }
 
@@ -96,6 +98,7 @@ impl Statistics {
         let mut stat = Statistics {
             total_nodes: 1,
             cut_nodes: 0,
+            table_hits: 0,
             cache_hit: HashMap::new(),
         };
         stat.cache_hit.insert(board_id, 1);
@@ -111,11 +114,12 @@ impl Statistics {
     pub(super) fn add(&mut self, other: &Statistics) {
         self.total_nodes += other.total_nodes;
         self.cut_nodes += other.cut_nodes;
+        self.table_hits += other.table_hits;
         Statistics::merge_maps(&mut self.cache_hit, &other.cache_hit);
     }
 
     pub(super) fn print_cache_stat(&self) {
-        let mut cache_stat_vec: Vec<u32> = self.cache_hit.iter().map(|(_k, v)| *v).collect();
+        let mut cache_stat_vec: Vec<u32> = self.cache_hit.values().copied().collect();
         cache_stat_vec.sort_unstable();
         cache_stat_vec.reverse();
         let total_len = cache_stat_vec.len();
@@ -17,87 +17,194 @@ You should have received a copy of the GNU General Public License
 along with game-2048-engine.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use crate::board::Board;
 use crate::board::State;
+use crate::board::{Board, BOARD_SIZE};
 use crate::direction::Direction;
-use crate::engine::engine_config::{EngineConfig, RandomCompleteness};
+use crate::engine::engine_config::{Algorithm, EngineConfig, RandomCompleteness};
 use crate::engine::evaluation;
 use crate::engine::moves::BestMove;
 use crate::engine::moves::Move;
 use crate::engine::moves::Statistics;
+use crate::engine::search::Search;
+use crate::engine::transposition;
 use crate::random;
-use crate::random::RndMove;
+use crate::random::SpawnRules;
 use std::cmp::max;
 use std::cmp::min;
 use std::cmp::Ordering;
 use std::mem::take;
-
-const BOARD_SIZE: usize = 4;
+use std::thread;
 
 #[derive(Debug, Default)]
-pub(super) struct Node {
-    pub(super) board: Board,
+pub(super) struct Node<const N: usize = BOARD_SIZE> {
+    pub(super) board: Board<N>,
     turn: Move,
     pub(super) value: i32,
-    pub(super) children: Option<Vec<Node>>,
+    /// static evaluation without penalties, computed once
+    static_value: Option<i32>,
+    /// weight of the random moves among other children of the chance node
+    chance: u64,
+    pub(super) children: Option<Vec<Node<N>>>,
 }
 
-impl Ord for Node {
+impl<const N: usize> Ord for Node<N> {
     fn cmp(&self, other: &Self) -> Ordering {
         other.value.cmp(&self.value)
     }
 }
 
-impl PartialOrd for Node {
+impl<const N: usize> PartialOrd for Node<N> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl PartialEq for Node {
+impl<const N: usize> PartialEq for Node<N> {
     fn eq(&self, other: &Self) -> bool {
         self.value == other.value
     }
 }
 
-impl Eq for Node {}
+impl<const N: usize> Eq for Node<N> {}
 
 //TODO estimate the possibility of cutting a node with non full filling or alpha-beta
 const PENALTY: i32 = 1_000_000;
 
-const DIRICTION_CYCLE: [Direction; 4] = [
-    Direction::Left,
-    Direction::Right,
-    Direction::Down,
-    Direction::Up,
-];
-
-impl Node {
-    pub(super) fn with_board(new_board: Board, turn: Move) -> Self {
+impl<const N: usize> Node<N> {
+    pub(super) fn with_board(new_board: Board<N>, turn: Move) -> Self {
         Node {
             board: new_board,
             turn,
             value: 0,
+            static_value: None,
+            chance: 1,
             children: None,
         }
     }
 
     /// Terminal case of recursion
-    fn as_terminal_leaf(&self) -> BestMove {
+    fn as_terminal_leaf(&self, search: &Search) -> BestMove {
         BestMove {
             turn: self.turn,
             local_id: 0,
             score: self.value,
-            stat: Statistics::new(self.board.get_board_id()),
+            stat: Statistics::new(self.table_key(search)),
+        }
+    }
+
+    /// Static evaluation of the board, 'value' holds search results so it is cached apart
+    fn static_evaluate(&mut self, config: &EngineConfig) -> i32 {
+        match self.static_value {
+            Some(score) => score,
+            None => {
+                let score = evaluation::evaluate(config.eval_fn, self);
+                self.static_value = Some(score);
+                score
+            }
+        }
+    }
+
+    /// Position found in transposition table
+    fn as_table_leaf(&self, search: &Search, score: i32) -> BestMove {
+        let mut leaf = self.as_terminal_leaf(search);
+        leaf.score = score;
+        leaf.stat.table_hits += 1;
+        leaf
+    }
+
+    /// Lost game or reached target tile, no search after it
+    fn is_terminal(&self, search: &Search) -> bool {
+        self.board.state == State::Lose || self.is_goal(search)
+    }
+
+    fn is_goal(&self, search: &Search) -> bool {
+        search
+            .goal
+            .is_some_and(|target| self.board.max_cell() >= target)
+    }
+
+    /// Static evaluation, reached target is better than any position without it
+    fn evaluate(&mut self, search: &Search) -> i32 {
+        let score = self.static_evaluate(search.config);
+        if self.is_goal(search) {
+            score + PENALTY
+        } else {
+            score
+        }
+    }
+
+    /// Key of the position in transposition table
+    fn table_key(&self, search: &Search) -> u64 {
+        if search.symmetric {
+            self.board.get_canonical_id()
+        } else {
+            self.board.get_board_id()
         }
     }
 
-    /// Use existing tree
-    pub(super) fn find_next_random_move(&mut self, random_move: Move) -> Node {
+    /// Part of the searched score added by the game score of the position.
+    /// The same board is reached with different scores, so the table keeps scores without it
+    fn score_offset(&self, search: &Search) -> i32 {
+        let offset = search.config.eval_fn.max_score * self.board.score as i32;
+        let negamax = matches!(
+            search.config.algorithm,
+            Algorithm::Negamax | Algorithm::NegamaxAlphaBeta | Algorithm::NegaScout
+        );
+        //negamax family keeps scores of the random player negated
+        if negamax && self.turn.is_human() {
+            -offset
+        } else {
+            offset
+        }
+    }
+
+    /// Score of the same position searched before at least to depth,
+    /// root is always searched to get the move
+    fn probe(&self, search: &Search, depth: u16, alpha: i32, beta: i32) -> Option<i32> {
+        if depth == search.root_depth {
+            return None;
+        }
+        let offset = self.score_offset(search);
+        let score = search.table.probe(
+            self.table_key(search),
+            self.turn.is_human(),
+            depth,
+            alpha.saturating_sub(offset),
+            beta.saturating_sub(offset),
+        )?;
+        Some(score + offset)
+    }
+
+    /// Save score searched with (alpha, beta) window
+    fn store(&self, search: &mut Search, depth: u16, score: i32, window: (i32, i32)) {
+        if search.is_aborted() {
+            return;
+        }
+        let (alpha, beta) = window;
+        search.table.store(
+            self.table_key(search),
+            self.turn.is_human(),
+            depth,
+            score - self.score_offset(search),
+            transposition::bound_of(score, alpha, beta),
+        );
+    }
+
+    /// Terminal case of recursion for negamax family
+    fn as_colored_leaf(&self, search: &Search, color: i8) -> BestMove {
+        let mut leaf = self.as_terminal_leaf(search);
+        leaf.score *= color as i32;
+        leaf
+    }
+
+    /// Use existing tree, a child with several spawns is found by the board after the last one
+    pub(super) fn find_next_random_move(&mut self, random_move: Move) -> Node<N> {
+        let rmove = random_move.unwrap_random();
+        self.board.set_move(rmove);
         if let Some(ref mut vec) = self.children {
             //TODO use map
             for node in vec {
-                if node.turn == random_move {
+                if node.board.same_cells(&self.board) {
                     let next_move = take(node);
                     return next_move;
                 }
@@ -105,12 +212,10 @@ impl Node {
         }
 
         //otherwise if not found, create new tree
-        let rmove = random_move.unwrap_random();
-        self.board.set_move(rmove);
         Node::with_board(self.board, random_move)
     }
 
-    fn gen_next_nodes(&mut self, config: &EngineConfig) -> &mut Option<Vec<Node>> {
+    fn gen_next_nodes(&mut self, config: &EngineConfig) -> &mut Option<Vec<Node<N>>> {
         if self.children.is_none() {
             let mut nodes = if self.turn.is_human() {
                 self.next_random_moves(config)
@@ -122,9 +227,14 @@ impl Node {
                 if config.order_moves {
                     for node in nodes.iter_mut() {
                         //unfortunately cache hits are not counted here
-                        node.value = evaluation::evaluate(config.eval_fn, &node);
+                        node.value = node.static_evaluate(config);
+                    }
+                    if self.turn.is_human() && matches!(config.algorithm, Algorithm::NegaScout) {
+                        //random player minimizes, worst positions first for principal variation
+                        nodes.sort_by(|a, b| b.cmp(a));
+                    } else {
+                        nodes.sort();
                     }
-                    nodes.sort();
                 }
                 self.children = Some(nodes);
             } else {
@@ -135,9 +245,9 @@ impl Node {
         &mut self.children
     }
 
-    fn next_human_moves(&self) -> Vec<Node> {
-        let mut nodes: Vec<Node> = Vec::with_capacity(4);
-        for dir in &DIRICTION_CYCLE {
+    fn next_human_moves(&self) -> Vec<Node<N>> {
+        let mut nodes: Vec<Node<N>> = Vec::with_capacity(4);
+        for dir in &Direction::ALL {
             let mut new_board = self.board;
             let moved = new_board.slide_to(*dir);
             if moved {
@@ -149,38 +259,75 @@ impl Node {
         nodes
     }
 
-    fn next_random_moves(&self, config: &EngineConfig) -> Vec<Node> {
-        match config.random_mode {
-            RandomCompleteness::Full => self.next_random_moves_full(),
-            RandomCompleteness::Ordered(count) => self.next_random_moves_limit(count),
-            RandomCompleteness::MonteCarlo(count) => self.next_random_moves_montecarlo(count),
+    /// Random moves by spawn rules, a child makes every spawn of the turn.
+    /// Limited random moves are limited for each spawn
+    fn next_random_moves(&self, config: &EngineConfig) -> Vec<Node<N>> {
+        let rules = &config.spawn_rules;
+        let weights = rules.values.weights();
+        let mut nodes = vec![Node::with_board(self.board, Move::default())];
+        for _ in 0..rules.tiles_per_turn {
+            let mut next_nodes = Vec::with_capacity(nodes.len());
+            for node in nodes {
+                if node.board.empty_count() == 0 {
+                    //board is filled before the end of the turn
+                    next_nodes.push(node);
+                    continue;
+                }
+                let spawns = match config.random_mode {
+                    RandomCompleteness::Full => node.next_random_moves_full(&weights),
+                    RandomCompleteness::Ordered(count) => {
+                        node.next_random_moves_limit(&weights, count)
+                    }
+                    RandomCompleteness::MonteCarlo(count) => {
+                        node.next_random_moves_montecarlo(rules, &weights, count)
+                    }
+                };
+                next_nodes.extend(spawns.into_iter().map(|mut spawn| {
+                    spawn.chance = spawn.chance.saturating_mul(node.chance);
+                    spawn
+                }));
+            }
+            nodes = next_nodes;
         }
+        nodes
     }
 
-    fn next_random_moves_limit(&self, limit: u8) -> Vec<Node> {
-        let mut nodes: Vec<Node> = Vec::with_capacity(limit as usize);
-        self.next_random_moves_value_limit(&mut nodes, 1, limit);
-        if (nodes.len() as u8) < limit {
-            let limit = limit - nodes.len() as u8;
-            self.next_random_moves_value_limit(&mut nodes, 2, limit);
+    fn next_random_moves_limit(&self, weights: &[(u8, u32)], limit: u8) -> Vec<Node<N>> {
+        let mut nodes: Vec<Node<N>> = Vec::with_capacity(limit as usize);
+        for &(value, weight) in weights {
+            let rest = limit.saturating_sub(nodes.len() as u8);
+            if rest == 0 && !nodes.is_empty() {
+                break;
+            }
+            self.next_random_moves_value_limit(&mut nodes, (value, weight), rest);
         }
         nodes
     }
 
-    fn next_random_moves_montecarlo(&self, mut limit: u8) -> Vec<Node> {
+    fn next_random_moves_montecarlo(
+        &self,
+        rules: &SpawnRules,
+        weights: &[(u8, u32)],
+        mut limit: u8,
+    ) -> Vec<Node<N>> {
         let mut rnd = random::get_rnd();
         let mut empty_count = self.board.empty_count();
 
         if empty_count < limit {
             limit = empty_count;
         }
-        let mut nodes: Vec<Node> = Vec::with_capacity(limit as usize);
+        let mut nodes: Vec<Node<N>> = Vec::with_capacity(limit as usize);
 
         for _i in 0..limit {
-            let next_move = rnd.next_move(empty_count);
+            let next_move = rules.next_move(&mut rnd, empty_count);
             let mut new_board = self.board;
             new_board.set_move(next_move);
-            let node = Node::with_board(new_board, Move::from_tuple(next_move));
+            let mut node = Node::with_board(new_board, Move::from_tuple(next_move));
+            let (value, _pos) = next_move;
+            node.chance = weights
+                .iter()
+                .find(|&&(exp, _weight)| exp == value)
+                .map_or(1, |&(_exp, weight)| weight as u64);
             nodes.push(node);
 
             empty_count -= 1;
@@ -189,24 +336,28 @@ impl Node {
         nodes
     }
 
-    fn next_random_moves_full(&self) -> Vec<Node> {
-        let mut nodes: Vec<Node> = Vec::with_capacity(30);
-        self.next_random_moves_value(&mut nodes, 1);
-        self.next_random_moves_value(&mut nodes, 2);
-        nodes.shrink_to_fit();
+    fn next_random_moves_full(&self, weights: &[(u8, u32)]) -> Vec<Node<N>> {
+        let empty_count = self.board.empty_count() as usize;
+        let mut nodes: Vec<Node<N>> = Vec::with_capacity(weights.len() * empty_count);
+        for &spawn in weights {
+            self.next_random_moves_value(&mut nodes, spawn);
+        }
         nodes
     }
 
-    fn next_random_moves_value(&self, nodes: &mut Vec<Node>, value: u8) {
+    /// spawn: (value, weight of the value)
+    fn next_random_moves_value(&self, nodes: &mut Vec<Node<N>>, spawn: (u8, u32)) {
         //TODO move to board
+        let (value, weight) = spawn;
         let mut c = 0u8;
-        for j in 0..BOARD_SIZE {
-            for i in 0..BOARD_SIZE {
-                if self.board.board[j][i] == 0 {
+        for j in 0..N {
+            for i in 0..N {
+                if self.board.cell(j, i) == 0 {
                     let mut new_board = self.board;
-                    new_board.board[j][i] = value;
+                    new_board.set_cell(j, i, value);
                     new_board.move_count += 1;
-                    let node = Node::with_board(new_board, Move::Random(value, c));
+                    let mut node = Node::with_board(new_board, Move::Random(value, c));
+                    node.chance = weight as u64;
                     nodes.push(node);
                     c += 1;
                 }
@@ -214,16 +365,19 @@ impl Node {
         }
     }
 
-    fn next_random_moves_value_limit(&self, nodes: &mut Vec<Node>, value: u8, limit: u8) {
+    /// spawn: (value, weight of the value)
+    fn next_random_moves_value_limit(&self, nodes: &mut Vec<Node<N>>, spawn: (u8, u32), limit: u8) {
         //TODO move to board
+        let (value, weight) = spawn;
         let mut c = 0u8;
-        'outer: for j in 0..BOARD_SIZE {
-            for i in 0..BOARD_SIZE {
-                if self.board.board[j][i] == 0 {
+        'outer: for j in 0..N {
+            for i in 0..N {
+                if self.board.cell(j, i) == 0 {
                     let mut new_board = self.board;
-                    new_board.board[j][i] = value;
+                    new_board.set_cell(j, i, value);
                     new_board.move_count += 1;
-                    let node = Node::with_board(new_board, Move::Random(value, c));
+                    let mut node = Node::with_board(new_board, Move::Random(value, c));
+                    node.chance = weight as u64;
                     nodes.push(node);
                     c += 1;
                     if c >= limit {
@@ -236,28 +390,125 @@ impl Node {
 }
 
 //algorithms
-impl Node {
+impl<const N: usize> Node<N> {
+    /// Search from the root with configured algorithm
+    pub(super) fn search(&mut self, search: &mut Search, depth: u16) -> BestMove {
+        const ALPHA: i32 = i32::MIN + 1;
+        const BETA: i32 = i32::MAX;
+        match search.config.algorithm {
+            Algorithm::Minimax => self.minimax(search, depth, true),
+            Algorithm::MinimaxAlphaBeta => self.minimax_alphabeta(search, depth, ALPHA, BETA, true),
+            Algorithm::Negamax => self.negamax(search, depth, 1),
+            Algorithm::NegamaxAlphaBeta => self.negamax_alphabeta(search, depth, ALPHA, BETA, 1),
+            Algorithm::NegaScout => self.negascout(search, depth, ALPHA, BETA, 1),
+            Algorithm::ExpectiMinimax => self.expectimax(search, depth, true, 1.0),
+            Algorithm::ExpectiMinimaxStar2 => {
+                self.expectimax_star(search, depth, ALPHA, BETA, true, 1.0)
+            }
+            Algorithm::Mcts(_) | Algorithm::RandomRollout(_) => {
+                unreachable!("Algorithm doesn't use node tree")
+            }
+        }
+    }
+
+    /// Search of the random move child of the root, score from the root point of view
+    fn search_child(&mut self, search: &mut Search, depth: u16) -> BestMove {
+        const ALPHA: i32 = i32::MIN + 1;
+        const BETA: i32 = i32::MAX;
+        match search.config.algorithm {
+            Algorithm::Minimax => self.minimax(search, depth, false),
+            Algorithm::MinimaxAlphaBeta => {
+                self.minimax_alphabeta(search, depth, ALPHA, BETA, false)
+            }
+            Algorithm::Negamax => -self.negamax(search, depth, -1),
+            Algorithm::NegamaxAlphaBeta => {
+                -self.negamax_alphabeta(search, depth, -BETA, -ALPHA, -1)
+            }
+            Algorithm::NegaScout => -self.negascout(search, depth, -BETA, -ALPHA, -1),
+            Algorithm::ExpectiMinimax => self.expectimax(search, depth, false, 1.0),
+            Algorithm::ExpectiMinimaxStar2 => {
+                self.expectimax_star(search, depth, ALPHA, BETA, false, 1.0)
+            }
+            Algorithm::Mcts(_) | Algorithm::RandomRollout(_) => {
+                unreachable!("Algorithm doesn't use node tree")
+            }
+        }
+    }
+
+    /// Root children are split between workers, every worker has own search state.
+    /// Children are searched with full window, so the result is the same as sequential
+    pub(super) fn search_parallel(&mut self, searches: &mut [Search], depth: u16) -> BestMove {
+        if depth == 0 || self.is_terminal(&searches[0]) {
+            return self.search(&mut searches[0], depth);
+        }
+
+        let nodes = self.gen_next_nodes(searches[0].config);
+        let vec = match nodes {
+            Some(ref mut vec) => vec,
+            None => return self.search(&mut searches[0], depth),
+        };
+
+        let workers = min(searches.len(), vec.len());
+        let mut jobs: Vec<Vec<(usize, &mut Node<N>)>> = (0..workers).map(|_| Vec::new()).collect();
+        for (index, node) in vec.iter_mut().enumerate() {
+            jobs[index % workers].push((index, node));
+        }
+
+        let mut results: Vec<(usize, BestMove)> = thread::scope(|scope| {
+            let handles: Vec<_> = jobs
+                .into_iter()
+                .zip(searches.iter_mut())
+                .map(|(job, search)| {
+                    scope.spawn(move || {
+                        job.into_iter()
+                            .map(|(index, node)| (index, node.search_child(search, depth - 1)))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("search worker panicked"))
+                .collect()
+        });
+
+        //merge in sequential order
+        results.sort_by_key(|(index, _)| *index);
+        let mut value = BestMove::new(i32::MIN);
+        for (index, best_move) in results {
+            max_score_move(best_move, &mut value, &vec[index], index);
+        }
+        self.value = value.score;
+        value
+    }
+
     pub(super) fn minimax_alphabeta(
         &mut self,
-        config: &EngineConfig,
+        search: &mut Search,
         depth: u16,
         mut alpha: i32,
         mut beta: i32,
         max_player: bool,
     ) -> BestMove {
-        if depth == 0 || self.board.state == State::Lose {
-            self.value = evaluation::evaluate(config.eval_fn, self);
-            return self.as_terminal_leaf();
+        if depth == 0 || self.is_terminal(search) || search.is_timeout() {
+            self.value = self.evaluate(search);
+            return self.as_terminal_leaf(search);
+        }
+
+        if let Some(score) = self.probe(search, depth, alpha, beta) {
+            self.value = score;
+            return self.as_table_leaf(search, score);
         }
+        let window = (alpha, beta);
 
         if max_player {
-            let nodes = self.gen_next_nodes(config);
-            let mut value = BestMove::new(i32::min_value());
+            let nodes = self.gen_next_nodes(search.config);
+            let mut value = BestMove::new(i32::MIN);
 
             if let Some(ref mut vec) = nodes {
                 for (index, node) in vec.iter_mut().enumerate() {
-                    let best_move = node.minimax_alphabeta(config, depth - 1, alpha, beta, false);
-                    max_score_move(best_move, &mut value, &node, index);
+                    let best_move = node.minimax_alphabeta(search, depth - 1, alpha, beta, false);
+                    max_score_move(best_move, &mut value, node, index);
 
                     alpha = max(alpha, value.score);
 
@@ -267,20 +518,21 @@ impl Node {
                     }
                 }
                 self.value = value.score;
+                self.store(search, depth, value.score, window);
                 value
             } else {
                 //penalty for losing
-                self.value = evaluation::evaluate(config.eval_fn, self) - PENALTY;
-                self.as_terminal_leaf()
+                self.value = self.static_evaluate(search.config) - PENALTY;
+                self.as_terminal_leaf(search)
             }
         } else {
-            let nodes = self.gen_next_nodes(config);
-            let mut value = BestMove::new(i32::max_value());
+            let nodes = self.gen_next_nodes(search.config);
+            let mut value = BestMove::new(i32::MAX);
 
             if let Some(ref mut vec) = nodes {
                 for (index, node) in vec.iter_mut().enumerate() {
-                    let best_move = node.minimax_alphabeta(config, depth - 1, alpha, beta, true);
-                    min_score_move(best_move, &mut value, &node, index);
+                    let best_move = node.minimax_alphabeta(search, depth - 1, alpha, beta, true);
+                    min_score_move(best_move, &mut value, node, index);
 
                     beta = min(beta, value.score);
                     if alpha >= beta {
@@ -289,107 +541,127 @@ impl Node {
                     }
                 }
                 self.value = value.score;
+                self.store(search, depth, value.score, window);
                 value
             } else {
                 //penalty for losing
-                self.value = evaluation::evaluate(config.eval_fn, self) + PENALTY;
-                self.as_terminal_leaf()
+                self.value = self.static_evaluate(search.config) + PENALTY;
+                self.as_terminal_leaf(search)
             }
         }
     }
 
     pub(super) fn minimax(
         &mut self,
-        config: &EngineConfig,
+        search: &mut Search,
         depth: u16,
         max_player: bool,
     ) -> BestMove {
-        if depth == 0 || self.board.state == State::Lose {
-            self.value = evaluation::evaluate(config.eval_fn, self);
-            return self.as_terminal_leaf();
+        if depth == 0 || self.is_terminal(search) || search.is_timeout() {
+            self.value = self.evaluate(search);
+            return self.as_terminal_leaf(search);
         }
 
-        let mut closure = |initial, penalty, cmp_fn: fn(BestMove, &mut BestMove, &Node, usize)| {
-            self.minimax_part(config, depth, max_player, initial, penalty, cmp_fn)
-        };
+        if let Some(score) = self.probe(search, depth, i32::MIN, i32::MAX) {
+            self.value = score;
+            return self.as_table_leaf(search, score);
+        }
+
+        let mut closure =
+            |initial, penalty, cmp_fn: fn(BestMove, &mut BestMove, &Node<N>, usize)| {
+                self.minimax_part(search, depth, max_player, initial, penalty, cmp_fn)
+            };
 
         if max_player {
-            closure(i32::min_value(), -PENALTY, max_score_move)
+            closure(i32::MIN, -PENALTY, max_score_move)
         } else {
-            closure(i32::max_value(), PENALTY, min_score_move)
+            closure(i32::MAX, PENALTY, min_score_move)
         }
     }
 
     fn minimax_part(
         &mut self,
-        config: &EngineConfig,
+        search: &mut Search,
         depth: u16,
         max_player: bool,
         initial: i32,
         penalty: i32,
-        cmp_fn: fn(BestMove, &mut BestMove, &Node, usize),
+        cmp_fn: fn(BestMove, &mut BestMove, &Node<N>, usize),
     ) -> BestMove {
-        let nodes = self.gen_next_nodes(config);
+        let nodes = self.gen_next_nodes(search.config);
         let mut value = BestMove::new(initial);
 
         if let Some(ref mut vec) = nodes {
             for (index, node) in vec.iter_mut().enumerate() {
-                let best_move = node.minimax(config, depth - 1, !max_player);
-                cmp_fn(best_move, &mut value, &node, index);
+                let best_move = node.minimax(search, depth - 1, !max_player);
+                cmp_fn(best_move, &mut value, node, index);
             }
             self.value = value.score;
+            self.store(search, depth, value.score, (i32::MIN, i32::MAX));
             value
         } else {
             //penalty for losing
-            self.value = evaluation::evaluate(config.eval_fn, self) + penalty;
-            self.as_terminal_leaf()
+            self.value = self.static_evaluate(search.config) + penalty;
+            self.as_terminal_leaf(search)
         }
     }
 
-    pub(super) fn negamax(&mut self, config: &EngineConfig, depth: u16, color: i8) -> BestMove {
-        if depth == 0 || self.board.state == State::Lose {
-            self.value = color as i32 * evaluation::evaluate(config.eval_fn, self);
-            return self.as_terminal_leaf();
+    pub(super) fn negamax(&mut self, search: &mut Search, depth: u16, color: i8) -> BestMove {
+        if depth == 0 || self.is_terminal(search) || search.is_timeout() {
+            self.value = color as i32 * self.evaluate(search);
+            return self.as_terminal_leaf(search);
         }
 
-        let nodes = self.gen_next_nodes(config);
-        let mut value = BestMove::new(i32::min_value());
+        if let Some(score) = self.probe(search, depth, i32::MIN, i32::MAX) {
+            self.value = score;
+            return self.as_table_leaf(search, score);
+        }
+
+        let nodes = self.gen_next_nodes(search.config);
+        let mut value = BestMove::new(i32::MIN);
 
         if let Some(ref mut vec) = nodes {
             for (index, node) in vec.iter_mut().enumerate() {
-                let best_move = -node.negamax(config, depth - 1, -color);
-                max_score_move(best_move, &mut value, &node, index);
+                let best_move = -node.negamax(search, depth - 1, -color);
+                max_score_move(best_move, &mut value, node, index);
             }
             self.value = value.score;
+            self.store(search, depth, value.score, (i32::MIN, i32::MAX));
             value
         } else {
             //penalty for losing
-            self.value = color as i32 * (evaluation::evaluate(config.eval_fn, self) - PENALTY);
-            self.as_terminal_leaf()
+            self.value = color as i32 * (self.static_evaluate(search.config) - PENALTY);
+            self.as_terminal_leaf(search)
         }
     }
 
     pub(super) fn negamax_alphabeta(
         &mut self,
-        config: &EngineConfig,
+        search: &mut Search,
         depth: u16,
         mut alpha: i32,
         beta: i32,
         color: i8,
     ) -> BestMove {
-        if depth == 0 || self.board.state == State::Lose {
-            self.value = color as i32 * evaluation::evaluate(config.eval_fn, self);
-            return self.as_terminal_leaf();
+        if depth == 0 || self.is_terminal(search) || search.is_timeout() {
+            self.value = color as i32 * self.evaluate(search);
+            return self.as_terminal_leaf(search);
+        }
+
+        if let Some(score) = self.probe(search, depth, alpha, beta) {
+            self.value = score;
+            return self.as_table_leaf(search, score);
         }
+        let window = (alpha, beta);
 
-        let nodes = self.gen_next_nodes(config);
-        let mut value = BestMove::new(i32::min_value());
+        let nodes = self.gen_next_nodes(search.config);
+        let mut value = BestMove::new(i32::MIN);
 
         if let Some(ref mut vec) = nodes {
             for (index, node) in vec.iter_mut().enumerate() {
-                debug_assert_ne!(beta, i32::min_value());
-                let best_move = -node.negamax_alphabeta(config, depth - 1, -beta, -alpha, -color);
-                max_score_move(best_move, &mut value, &node, index);
+                debug_assert_ne!(beta, i32::MIN);
+                let best_move = -node.negamax_alphabeta(search, depth - 1, -beta, -alpha, -color);
+                max_score_move(best_move, &mut value, node, index);
 
                 alpha = max(alpha, value.score);
                 if alpha >= beta {
@@ -398,19 +670,389 @@ impl Node {
                 }
             }
             self.value = value.score;
+            self.store(search, depth, value.score, window);
+            value
+        } else {
+            //penalty for losing
+            self.value = color as i32 * (self.static_evaluate(search.config) - PENALTY);
+            self.as_terminal_leaf(search)
+        }
+    }
+
+    /// Principal variation search, node values are stored from the human point of view
+    pub(super) fn negascout(
+        &mut self,
+        search: &mut Search,
+        depth: u16,
+        mut alpha: i32,
+        beta: i32,
+        color: i8,
+    ) -> BestMove {
+        if depth == 0 || self.is_terminal(search) || search.is_timeout() {
+            self.value = self.evaluate(search);
+            return self.as_colored_leaf(search, color);
+        }
+
+        if let Some(score) = self.probe(search, depth, alpha, beta) {
+            self.value = color as i32 * score;
+            return self.as_table_leaf(search, score);
+        }
+        let window = (alpha, beta);
+
+        let nodes = self.gen_next_nodes(search.config);
+        let mut value = BestMove::new(i32::MIN);
+
+        if let Some(ref mut vec) = nodes {
+            for (index, node) in vec.iter_mut().enumerate() {
+                let best_move = if index == 0 {
+                    -node.negascout(search, depth - 1, -beta, -alpha, -color)
+                } else {
+                    //null window search
+                    let mut best_move =
+                        -node.negascout(search, depth - 1, -alpha - 1, -alpha, -color);
+                    if alpha < best_move.score && best_move.score < beta {
+                        //fail high, re-search with full window
+                        let stat = best_move.stat;
+                        best_move =
+                            -node.negascout(search, depth - 1, -beta, -best_move.score, -color);
+                        best_move.stat.add(&stat);
+                    }
+                    best_move
+                };
+                max_score_move(best_move, &mut value, node, index);
+
+                alpha = max(alpha, value.score);
+                if alpha >= beta {
+                    value.stat.cut_nodes += 1;
+                    break;
+                }
+            }
+            self.value = color as i32 * value.score;
+            self.store(search, depth, value.score, window);
             value
         } else {
             //penalty for losing
-            self.value = color as i32 * (evaluation::evaluate(config.eval_fn, self) - PENALTY);
-            self.as_terminal_leaf()
+            self.value = self.static_evaluate(search.config) - PENALTY;
+            self.as_colored_leaf(search, color)
+        }
+    }
+
+    /// Expectimax, 'probability' is the cumulative chance to reach the node
+    pub(super) fn expectimax(
+        &mut self,
+        search: &mut Search,
+        depth: u16,
+        max_player: bool,
+        probability: f64,
+    ) -> BestMove {
+        if depth == 0 || self.is_terminal(search) || search.is_timeout() {
+            self.value = self.evaluate(search);
+            return self.as_terminal_leaf(search);
+        }
+
+        if probability < search.config.probability_cutoff {
+            //unlikely branch
+            self.value = self.evaluate(search);
+            let mut leaf = self.as_terminal_leaf(search);
+            leaf.stat.cut_nodes += 1;
+            return leaf;
+        }
+
+        if let Some(score) = self.probe(search, depth, i32::MIN, i32::MAX) {
+            self.value = score;
+            return self.as_table_leaf(search, score);
+        }
+
+        let nodes = self.gen_next_nodes(search.config);
+
+        if let Some(ref mut vec) = nodes {
+            let value = if max_player {
+                let mut value = BestMove::new(i32::MIN);
+                for (index, node) in vec.iter_mut().enumerate() {
+                    let best_move = node.expectimax(search, depth - 1, false, probability);
+                    max_score_move(best_move, &mut value, node, index);
+                }
+                value
+            } else {
+                let mut value = BestMove::new(0);
+                let mut chance = ChanceScore::default();
+                let total: u128 = vec.iter().map(|node| node.chance as u128).sum();
+                for node in vec.iter_mut() {
+                    let node_probability = probability * node.chance as f64 / total as f64;
+                    let best_move = node.expectimax(search, depth - 1, true, node_probability);
+                    chance.add(node.chance, best_move.score);
+                    value.stat.add(&best_move.stat);
+                }
+                value.score = chance.expected();
+                value
+            };
+            self.value = value.score;
+            self.store(search, depth, value.score, (i32::MIN, i32::MAX));
+            value
+        } else {
+            //penalty for losing, random player always has a move
+            let penalty = if max_player { PENALTY } else { 0 };
+            self.value = self.static_evaluate(search.config) - penalty;
+            self.as_terminal_leaf(search)
         }
     }
+
+    /// Expectimax with alpha-beta on max nodes, Star1 and Star2 pruning on chance nodes.
+    /// Chance node bounds come from the range of the evaluation function
+    pub(super) fn expectimax_star(
+        &mut self,
+        search: &mut Search,
+        depth: u16,
+        mut alpha: i32,
+        beta: i32,
+        max_player: bool,
+        probability: f64,
+    ) -> BestMove {
+        if depth == 0 || self.is_terminal(search) || search.is_timeout() {
+            self.value = self.evaluate(search);
+            return self.as_terminal_leaf(search);
+        }
+
+        if probability < search.config.probability_cutoff {
+            //unlikely branch
+            self.value = self.evaluate(search);
+            let mut leaf = self.as_terminal_leaf(search);
+            leaf.stat.cut_nodes += 1;
+            return leaf;
+        }
+
+        if let Some(score) = self.probe(search, depth, alpha, beta) {
+            self.value = score;
+            return self.as_table_leaf(search, score);
+        }
+        let window = (alpha, beta);
+
+        let nodes = self.gen_next_nodes(search.config);
+
+        if let Some(ref mut vec) = nodes {
+            let value = if max_player {
+                let mut value = BestMove::new(i32::MIN);
+                for (index, node) in vec.iter_mut().enumerate() {
+                    let best_move =
+                        node.expectimax_star(search, depth - 1, alpha, beta, false, probability);
+                    max_score_move(best_move, &mut value, node, index);
+
+                    alpha = max(alpha, value.score);
+                    if alpha >= beta {
+                        value.stat.cut_nodes += 1;
+                        break;
+                    }
+                }
+                value
+            } else {
+                chance_star(vec, search, depth, window, probability)
+            };
+            self.value = value.score;
+            self.store(search, depth, value.score, window);
+            value
+        } else {
+            //penalty for losing, random player always has a move
+            let penalty = if max_player { PENALTY } else { 0 };
+            self.value = self.static_evaluate(search.config) - penalty;
+            self.as_terminal_leaf(search)
+        }
+    }
+
+    /// Star2 probing, lower bound of the max node searching only its first move
+    fn probe_first_move(
+        &mut self,
+        search: &mut Search,
+        depth: u16,
+        window: (i32, i32),
+        probability: f64,
+    ) -> BestMove {
+        let (alpha, beta) = window;
+        if depth > 0 && !self.is_terminal(search) && probability >= search.config.probability_cutoff
+        {
+            if let Some(ref mut vec) = self.gen_next_nodes(search.config) {
+                return vec[0].expectimax_star(search, depth - 1, alpha, beta, false, probability);
+            }
+        }
+        self.expectimax_star(search, depth, alpha, beta, true, probability)
+    }
+}
+
+/// Chance node of expectimax_star, children are max nodes
+fn chance_star<const N: usize>(
+    vec: &mut [Node<N>],
+    search: &mut Search,
+    depth: u16,
+    window: (i32, i32),
+    probability: f64,
+) -> BestMove {
+    let (_, beta) = window;
+    let (low, high) = evaluation::bounds::<N>(search.config.eval_fn);
+    //losing is the worst leaf, reaching target is the best one
+    let goal = if search.goal.is_some() { PENALTY } else { 0 };
+    let low = low.saturating_sub(PENALTY as i64);
+    let high = high.saturating_add(goal as i64);
+    //every score is inside of the widest window of the search
+    let low = low.max(i32::MIN as i64 + 1) as i32;
+    let high = high.min(i32::MAX as i64 - 1) as i32;
+
+    let total: u128 = vec.iter().map(|node| node.chance as u128).sum();
+    let mut value = BestMove::new(0);
+    let mut bounds = ChanceBounds::new(total, low, high);
+    let mut lower = vec![low; vec.len()];
+
+    //Star2, lower bounds of children by probing, no fail high possible otherwise
+    if beta <= high {
+        for (index, node) in vec.iter_mut().enumerate() {
+            let chance = node.chance;
+            let node_probability = probability * chance as f64 / total as f64;
+            let (_, child_beta) = bounds.child_window(chance, low, window);
+            let probe =
+                node.probe_first_move(search, depth - 1, (low - 1, child_beta), node_probability);
+            value.stat.add(&probe.stat);
+
+            lower[index] = max(low, probe.score);
+            bounds.raise_lower(chance, low, lower[index]);
+            if let Some(score) = bounds.cutoff(window) {
+                value.score = score;
+                value.stat.cut_nodes += 1;
+                return value;
+            }
+        }
+    }
+
+    //Star1, search with windows which cut off by the bounds of the rest children
+    for (index, node) in vec.iter_mut().enumerate() {
+        if let Some(score) = bounds.cutoff(window) {
+            value.score = score;
+            value.stat.cut_nodes += 1;
+            return value;
+        }
+        let chance = node.chance;
+        let node_probability = probability * chance as f64 / total as f64;
+        let (child_alpha, child_beta) = bounds.child_window(chance, lower[index], window);
+        let best_move = node.expectimax_star(
+            search,
+            depth - 1,
+            child_alpha,
+            child_beta,
+            true,
+            node_probability,
+        );
+        value.stat.add(&best_move.stat);
+        bounds.add(chance, lower[index], best_move.score);
+    }
+
+    //all children searched, bounds are equal
+    value.score = bounds.cutoff(window).unwrap_or_else(|| bounds.lower());
+    value
+}
+
+/// Bounds of the chance node value while children are searched
+struct ChanceBounds {
+    /// probability-weighted sum of searched children
+    sum: i128,
+    /// total chance of all children
+    weight: i128,
+    /// chance of children not searched yet
+    rest_weight: i128,
+    /// weighted sum of lower bounds of children not searched yet
+    rest_lower: i128,
+    /// upper bound of any child
+    high: i128,
+}
+
+impl ChanceBounds {
+    fn new(total: u128, low: i32, high: i32) -> Self {
+        ChanceBounds {
+            sum: 0,
+            weight: total as i128,
+            rest_weight: total as i128,
+            rest_lower: total as i128 * low as i128,
+            high: high as i128,
+        }
+    }
+
+    /// Weighted average rounds down as in ChanceScore
+    fn lower(&self) -> i32 {
+        (self.sum + self.rest_lower).div_euclid(self.weight) as i32
+    }
+
+    fn upper(&self) -> i32 {
+        (self.sum + self.rest_weight * self.high).div_euclid(self.weight) as i32
+    }
+
+    /// Score outside of the window, fail-soft
+    fn cutoff(&self, window: (i32, i32)) -> Option<i32> {
+        let (alpha, beta) = window;
+        let upper = self.upper();
+        if upper <= alpha {
+            return Some(upper);
+        }
+        let lower = self.lower();
+        if lower >= beta {
+            return Some(lower);
+        }
+        None
+    }
+
+    /// Better lower bound of the child found by probing
+    fn raise_lower(&mut self, chance: u64, old: i32, new: i32) {
+        self.rest_lower += chance as i128 * (new - old) as i128;
+    }
+
+    /// Window of the next child, the child score outside of it cuts off the chance node
+    fn child_window(&self, chance: u64, lower: i32, window: (i32, i32)) -> (i32, i32) {
+        let (alpha, beta) = window;
+        let chance = chance as i128;
+        let weight = self.rest_weight - chance;
+        let rest_lower = self.rest_lower - chance * lower as i128;
+
+        //rest children at upper bound still don't reach alpha
+        let alpha_sum = (alpha as i128 + 1) * self.weight - self.sum - weight * self.high - 1;
+        let child_alpha = alpha_sum.div_euclid(chance);
+        //rest children at lower bound still reach beta
+        let beta_sum = beta as i128 * self.weight - self.sum - rest_lower;
+        let child_beta = -(-beta_sum).div_euclid(chance);
+
+        //child score never leaves its own bounds
+        let child_alpha = child_alpha.max(lower as i128 - 1).min(self.high);
+        let child_beta = child_beta.max(lower as i128).min(self.high + 1);
+        (child_alpha as i32, child_beta as i32)
+    }
+
+    /// Child searched
+    fn add(&mut self, chance: u64, lower: i32, score: i32) {
+        let chance = chance as i128;
+        self.sum += chance * score as i128;
+        self.rest_weight -= chance;
+        self.rest_lower -= chance * lower as i128;
+    }
+}
+
+/// Probability-weighted sum of random move scores
+#[derive(Default)]
+struct ChanceScore {
+    sum: i128,
+    weight: i128,
 }
 
-fn max_score_move(
+impl ChanceScore {
+    fn add(&mut self, chance: u64, score: i32) {
+        let chance = chance as i128;
+        self.sum += chance * score as i128;
+        self.weight += chance;
+    }
+
+    /// Weights are normalized, so limited random moves keep the average
+    fn expected(&self) -> i32 {
+        self.sum.div_euclid(self.weight) as i32
+    }
+}
+
+fn max_score_move<const N: usize>(
     mut best_move: BestMove,
     current_value: &mut BestMove,
-    node: &Node,
+    node: &Node<N>,
     index: usize,
 ) {
     if best_move.score > current_value.score {
@@ -426,10 +1068,10 @@ fn max_score_move(
     }
 }
 
-fn min_score_move(
+fn min_score_move<const N: usize>(
     mut best_move: BestMove,
     current_value: &mut BestMove,
-    node: &Node,
+    node: &Node<N>,
     index: usize,
 ) {
     if best_move.score < current_value.score {
@@ -444,3 +1086,333 @@ fn min_score_move(
         current_value.stat.add(&best_move.stat);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board;
+    use crate::engine::evaluation::Weights;
+    use crate::engine::transposition::TranspositionTable;
+    use crate::random::SpawnValues;
+
+    fn max_cell_config(random_mode: RandomCompleteness) -> EngineConfig {
+        let weights = Weights {
+            max_cell: 10,
+            max_score: 0,
+            monotonicity: 0,
+            smoothness: 0,
+            std_dev: 0,
+            free_space: 0,
+            snakeiness: 0,
+        };
+        EngineConfig {
+            random_mode,
+            ..EngineConfig::new(1, weights, Algorithm::ExpectiMinimax)
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn expectimax_chance_node_average() {
+        let mut board = Board::new();
+        board.set_cells(
+           [[1, 1, 1, 1],
+            [1, 1, 1, 1],
+            [1, 1, 1, 1],
+            [1, 1, 1, 0]]);
+        let mut node = Node::with_board(board, Move::Human(Direction::Left));
+
+        let config = max_cell_config(RandomCompleteness::Full);
+        let mut table = TranspositionTable::new(config.transposition_size);
+        let actual = node.expectimax(&mut Search::new(&config, &mut table), 1, false, 1.0);
+        // 90% of 2-tile and 10% of 4-tile
+        assert_eq!(actual.score, (20 * 9 + 40) / 10);
+        assert_eq!(actual.stat.total_nodes, 2);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn expectimax_limited_chance_node() {
+        let mut board = Board::new();
+        board.set_cells(
+           [[1, 1, 1, 1],
+            [1, 1, 1, 1],
+            [1, 1, 1, 1],
+            [1, 1, 0, 0]]);
+        let mut node = Node::with_board(board, Move::Human(Direction::Left));
+
+        let config = max_cell_config(RandomCompleteness::Ordered(2));
+        let mut table = TranspositionTable::new(config.transposition_size);
+        let actual = node.expectimax(&mut Search::new(&config, &mut table), 1, false, 1.0);
+        // only 2-tiles are expanded
+        assert_eq!(actual.score, 20);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn expectimax_chance_node_by_spawn_rules() {
+        let mut board = Board::new();
+        board.set_cells(
+           [[1, 1, 1, 1],
+            [1, 1, 1, 1],
+            [1, 1, 1, 1],
+            [1, 1, 0, 0]]);
+        let mut node = Node::with_board(board, Move::Human(Direction::Left));
+
+        let mut config = max_cell_config(RandomCompleteness::Full);
+        config.spawn_rules = SpawnRules {
+            values: SpawnValues::Weighted(vec![(1, 1), (3, 3)]),
+            tiles_per_turn: 2,
+            starting_tiles: 2,
+        };
+        let mut table = TranspositionTable::new(config.transposition_size);
+        let actual = node.expectimax(&mut Search::new(&config, &mut table), 1, false, 1.0);
+        // both cells are filled, 8-tile is in 15 of 16 turns
+        assert_eq!(actual.score, (20 + 80 * 15) / 16);
+        // 2 values of the first spawn, each followed by 2 values in the other cell
+        assert_eq!(actual.stat.total_nodes, 8);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn lost_node_penalty_on_revisit() {
+        board::load_cache();
+        let mut board = Board::new();
+        board.set_cells(
+           [[1, 2, 1, 2],
+            [2, 1, 2, 1],
+            [1, 2, 1, 2],
+            [2, 1, 2, 1]]);
+        let mut node = Node::with_board(board, Move::Random(1, 0));
+
+        let config = max_cell_config(RandomCompleteness::Full);
+        let mut table = TranspositionTable::new(config.transposition_size);
+        let mut search = Search::new(&config, &mut table);
+        for _ in 0..3 {
+            let actual = node.negascout(&mut search, 2, i32::MIN + 1, i32::MAX, 1);
+            assert_eq!(actual.score, 40 - PENALTY);
+            let actual = node.expectimax(&mut search, 2, true, 1.0);
+            assert_eq!(actual.score, 40 - PENALTY);
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn random_moves_worst_first_only_in_negascout() {
+        let mut board = Board::new();
+        board.set_cells(
+           [[1, 1, 1, 1],
+            [1, 1, 1, 1],
+            [1, 1, 1, 1],
+            [1, 1, 1, 0]]);
+
+        let mut config = max_cell_config(RandomCompleteness::Full);
+        for &(algorithm, expected) in &[
+            (Algorithm::MinimaxAlphaBeta, [40, 20]),
+            (Algorithm::NegamaxAlphaBeta, [40, 20]),
+            (Algorithm::ExpectiMinimax, [40, 20]),
+            (Algorithm::NegaScout, [20, 40]),
+        ] {
+            config.algorithm = algorithm;
+            let mut node = Node::with_board(board, Move::Human(Direction::Left));
+            let nodes = node.gen_next_nodes(&config).as_ref().unwrap();
+            let actual: Vec<i32> = nodes.iter().map(|node| node.value).collect();
+            assert_eq!(actual, expected);
+        }
+    }
+
+    fn mid_game_config(algorithm: Algorithm, transposition_size: usize) -> EngineConfig {
+        let weights = Weights {
+            max_cell: 30,
+            max_score: 10,
+            monotonicity: 100,
+            smoothness: 50,
+            std_dev: 0,
+            free_space: 300,
+            snakeiness: 0,
+        };
+        EngineConfig {
+            transposition_size,
+            ..EngineConfig::new(4, weights.normalize(), algorithm)
+        }
+    }
+
+    #[rustfmt::skip]
+    fn mid_game_node() -> Node {
+        let mut board = Board::new();
+        board.set_cells(
+           [[1, 2, 3, 0],
+            [0, 1, 4, 2],
+            [2, 0, 1, 0],
+            [0, 0, 0, 1]]);
+        Node::with_board(board, Move::default())
+    }
+
+    fn search_node(config: &EngineConfig) -> BestMove {
+        let mut table = TranspositionTable::new(config.transposition_size);
+        let mut search = Search::new(config, &mut table);
+        search.root_depth = config.depth;
+        mid_game_node().search(&mut search, config.depth)
+    }
+
+    #[test]
+    fn negascout_equals_negamax_alphabeta() {
+        board::load_cache();
+        let expected = search_node(&mid_game_config(Algorithm::NegamaxAlphaBeta, 0));
+        let actual = search_node(&mid_game_config(Algorithm::NegaScout, 0));
+
+        assert_eq!(actual.score, expected.score);
+        assert_eq!(actual.turn, expected.turn);
+    }
+
+    #[test]
+    fn transposition_table_keeps_score() {
+        board::load_cache();
+        for &algorithm in &[
+            Algorithm::Minimax,
+            Algorithm::MinimaxAlphaBeta,
+            Algorithm::NegamaxAlphaBeta,
+            Algorithm::NegaScout,
+            Algorithm::ExpectiMinimax,
+            Algorithm::ExpectiMinimaxStar2,
+        ] {
+            let expected = search_node(&mid_game_config(algorithm, 0));
+            let actual = search_node(&mid_game_config(algorithm, 1 << 16));
+
+            assert_eq!(actual.score, expected.score);
+            assert_eq!(actual.turn, expected.turn);
+            assert_eq!(expected.stat.table_hits, 0);
+            assert!(actual.stat.table_hits > 0);
+            assert!(actual.stat.total_nodes < expected.stat.total_nodes);
+        }
+    }
+
+    #[test]
+    fn transposition_table_ignores_game_score() {
+        board::load_cache();
+        let higher_score = || {
+            let mut node = mid_game_node();
+            node.board.score = 1000;
+            node
+        };
+        for &algorithm in &[
+            Algorithm::Minimax,
+            Algorithm::MinimaxAlphaBeta,
+            Algorithm::NegamaxAlphaBeta,
+            Algorithm::NegaScout,
+            Algorithm::ExpectiMinimax,
+            Algorithm::ExpectiMinimaxStar2,
+        ] {
+            let config = mid_game_config(algorithm, 0);
+            let mut table = TranspositionTable::new(config.transposition_size);
+            let mut search = Search::new(&config, &mut table);
+            search.root_depth = config.depth;
+            let expected = higher_score().search(&mut search, config.depth);
+
+            //the same positions are in the table with lower scores
+            let config = mid_game_config(algorithm, 1 << 16);
+            let mut table = TranspositionTable::new(config.transposition_size);
+            let mut search = Search::new(&config, &mut table);
+            search.root_depth = config.depth;
+            mid_game_node().search(&mut search, config.depth);
+            let actual = higher_score().search(&mut search, config.depth);
+
+            assert_eq!(actual.score, expected.score);
+            assert!(actual.stat.table_hits > 0);
+        }
+    }
+
+    #[test]
+    fn parallel_search_equals_sequential() {
+        board::load_cache();
+        for &algorithm in &[
+            Algorithm::Minimax,
+            Algorithm::MinimaxAlphaBeta,
+            Algorithm::Negamax,
+            Algorithm::NegamaxAlphaBeta,
+            Algorithm::NegaScout,
+            Algorithm::ExpectiMinimax,
+            Algorithm::ExpectiMinimaxStar2,
+        ] {
+            let config = mid_game_config(algorithm, 1 << 12);
+            let expected = search_node(&config);
+
+            let mut tables: Vec<TranspositionTable> = (0..3)
+                .map(|_| TranspositionTable::new(config.transposition_size))
+                .collect();
+            let mut searches: Vec<Search> = tables
+                .iter_mut()
+                .map(|table| Search::new(&config, table))
+                .collect();
+            let actual = mid_game_node().search_parallel(&mut searches, config.depth);
+
+            assert_eq!(actual.score, expected.score);
+            assert_eq!(actual.turn, expected.turn);
+            assert_eq!(actual.local_id, expected.local_id);
+        }
+    }
+
+    #[test]
+    fn expectimax_probability_cutoff() {
+        board::load_cache();
+        let mut config = mid_game_config(Algorithm::ExpectiMinimax, 0);
+        config.depth = 2;
+        let expected = search_node(&config);
+
+        // every random move of the mid game board is less likely than 50%
+        config.depth = 4;
+        config.probability_cutoff = 0.5;
+        let actual = search_node(&config);
+
+        assert_eq!(actual.score, expected.score);
+        assert_eq!(actual.turn, expected.turn);
+        assert!(actual.stat.cut_nodes > 0);
+
+        config.probability_cutoff = 0.0;
+        let full = search_node(&config);
+        assert_eq!(full.stat.cut_nodes, 0);
+        assert!(actual.stat.total_nodes < full.stat.total_nodes);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn expectimax_cutoff_keeps_goal_bonus() {
+        board::load_cache();
+        let mut config = mid_game_config(Algorithm::ExpectiMinimax, 0);
+        config.probability_cutoff = 0.5;
+        config.target = Some(64);
+        for &algorithm in &[Algorithm::ExpectiMinimax, Algorithm::ExpectiMinimaxStar2] {
+            config.algorithm = algorithm;
+            let mut board = Board::new();
+            board.set_cells(
+               [[5, 0, 0, 0],
+                [5, 0, 0, 0],
+                [0, 0, 0, 0],
+                [0, 0, 0, 0]]);
+            let mut table = TranspositionTable::new(config.transposition_size);
+            let mut search = Search::new(&config, &mut table);
+            search.root_depth = config.depth;
+            let actual = Node::with_board(board, Move::default()).search(&mut search, config.depth);
+
+            assert!(actual.turn == Move::Human(Direction::Up) || actual.turn == Move::Human(Direction::Down));
+            assert!(actual.score > PENALTY);
+            assert!(actual.stat.cut_nodes > 0);
+        }
+    }
+
+    #[test]
+    fn expectimax_star_equals_expectimax() {
+        board::load_cache();
+        for &depth in &[2, 3, 4, 5] {
+            let mut config = mid_game_config(Algorithm::ExpectiMinimax, 0);
+            config.depth = depth;
+            let expected = search_node(&config);
+            config.algorithm = Algorithm::ExpectiMinimaxStar2;
+            let actual = search_node(&config);
+
+            assert_eq!(actual.score, expected.score);
+            assert_eq!(actual.turn, expected.turn);
+            assert!(actual.stat.total_nodes <= expected.stat.total_nodes);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+/* rollout.rs -- random games.
+Copyright (C) 2021 fuggy
+
+This file is part of game-2048-engine.
+
+game-2048-engine is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+game-2048-engine is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with game-2048-engine.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::board::Board;
+use crate::direction::Direction;
+use crate::engine::engine_config::{Rollout, RolloutPolicy, RolloutTarget};
+use crate::game::Game;
+use crate::random;
+use crate::random::{RndMove, SpawnRules, Xoshiro256};
+
+const CORNER_DIRECTIONS: [Direction; 4] = [
+    Direction::Down,
+    Direction::Left,
+    Direction::Right,
+    Direction::Up,
+];
+
+impl RolloutPolicy {
+    /// Next move of the random game, none if no move is possible
+    fn choose<const N: usize>(self, board: &Board<N>, rnd: &mut Xoshiro256) -> Option<Direction> {
+        match self {
+            RolloutPolicy::Uniform => {
+                let mut legal = Direction::ALL;
+                let mut count = 0;
+                for &dir in &Direction::ALL {
+                    if is_legal(board, dir) {
+                        legal[count] = dir;
+                        count += 1;
+                    }
+                }
+                if count == 0 {
+                    None
+                } else {
+                    Some(legal[rnd.below(count as u32) as usize])
+                }
+            }
+            RolloutPolicy::Corner => CORNER_DIRECTIONS
+                .iter()
+                .copied()
+                .find(|&dir| is_legal(board, dir)),
+        }
+    }
+}
+
+/// Does the move change the board
+fn is_legal<const N: usize>(board: &Board<N>, dir: Direction) -> bool {
+    let mut next = *board;
+    next.slide_to(dir)
+}
+
+impl RolloutTarget {
+    fn result<const N: usize>(self, board: &Board<N>) -> f64 {
+        match self {
+            RolloutTarget::Score => board.score as f64,
+            RolloutTarget::MaxCell => board.max_cell() as f64,
+        }
+    }
+}
+
+/// Play the game to the end, human moves first
+pub(super) fn play_out<const N: usize>(
+    game: &mut Game<N>,
+    policy: RolloutPolicy,
+    rnd: &mut Xoshiro256,
+) {
+    while let Some(dir) = policy.choose(&game.board, rnd) {
+        if !game.make_move(dir) {
+            break;
+        }
+    }
+}
+
+/// Average result of random games for every possible move
+pub(super) fn average_results<const N: usize>(
+    board: Board<N>,
+    rules: &SpawnRules,
+    rollout: Rollout,
+) -> Vec<(Direction, f64)> {
+    let mut rnd = random::get_rnd();
+    let mut results = Vec::with_capacity(Direction::ALL.len());
+
+    for &dir in &Direction::ALL {
+        if !is_legal(&board, dir) {
+            continue;
+        }
+
+        let mut sum = 0.0;
+        for _ in 0..rollout.count {
+            let seed = rnd.next_u64();
+            let mut game = Game::from_board(board, rules.clone(), Xoshiro256::with_seed(seed));
+            game.make_move(dir);
+            play_out(&mut game, rollout.policy, &mut rnd);
+            sum += rollout.target.result(&game.board);
+        }
+        results.push((dir, sum / rollout.count.max(1) as f64));
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board;
+    use crate::board::State;
+
+    #[test]
+    fn should_play_to_the_end() {
+        board::load_cache();
+        let mut rnd = Xoshiro256::with_seed(7);
+        for &policy in &[RolloutPolicy::Uniform, RolloutPolicy::Corner] {
+            let mut game: Game = Game::with_seed(3);
+            play_out(&mut game, policy, &mut rnd);
+
+            assert_eq!(game.board.state, State::Lose);
+            assert!(!game.board.can_move());
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn should_choose_uniformly_from_legal_moves() {
+        board::load_cache();
+        let mut board = Board::new();
+        board.set_cells(
+           [[1, 2, 1, 2],
+            [2, 1, 2, 1],
+            [1, 2, 1, 2],
+            [1, 2, 1, 2]]);
+        let mut rnd = Xoshiro256::with_seed(7);
+        let mut down = 0;
+        for _ in 0..1000 {
+            match RolloutPolicy::Uniform.choose(&board, &mut rnd) {
+                Some(Direction::Down) => down += 1,
+                Some(Direction::Up) => {}
+                other => panic!("impossible move {:?}", other),
+            }
+        }
+        assert!((400..600).contains(&down));
+
+        let mut cells = board.cells();
+        cells[3] = [2, 1, 2, 1];
+        board.set_cells(cells);
+        assert_eq!(RolloutPolicy::Uniform.choose(&board, &mut rnd), None);
+        assert_eq!(RolloutPolicy::Corner.choose(&board, &mut rnd), None);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn should_skip_impossible_moves() {
+        board::load_cache();
+        let mut board = Board::new();
+        board.set_cells(
+           [[1, 2, 1, 2],
+            [2, 1, 2, 1],
+            [1, 2, 1, 2],
+            [2, 1, 2, 0]]);
+        let rollout = Rollout {
+            count: 10,
+            policy: RolloutPolicy::Uniform,
+            target: RolloutTarget::MaxCell,
+        };
+
+        let results = average_results(board, &SpawnRules::default(), rollout);
+        let dirs: Vec<Direction> = results.iter().map(|&(dir, _avg)| dir).collect();
+        assert_eq!(dirs, vec![Direction::Right, Direction::Down]);
+        assert!(results.iter().all(|&(_dir, avg)| avg >= 4.0));
+    }
+}
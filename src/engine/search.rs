@@ -0,0 +1,155 @@
+/* search.rs -- state of a single search.
+Copyright (C) 2021 fuggy
+
+This file is part of game-2048-engine.
+
+game-2048-engine is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+game-2048-engine is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with game-2048-engine.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::engine::engine_config::{EngineConfig, RandomCompleteness};
+use crate::engine::transposition::TranspositionTable;
+use std::time::Instant;
+
+/// Nodes between time checks (power of two), reading the clock is not free
+const CHECK_PERIOD: u32 = 1024;
+
+/// State shared by all nodes of a single search
+pub(super) struct Search<'a> {
+    pub(super) config: &'a EngineConfig,
+    pub(super) table: &'a mut TranspositionTable,
+    /// depth of the root node
+    pub(super) root_depth: u16,
+    /// symmetric positions share table entries, canonical ids are not computed without table
+    pub(super) symmetric: bool,
+    /// tile ending the search, none if the root already has it
+    pub(super) goal: Option<u64>,
+    deadline: Option<Instant>,
+    node_count: u32,
+    aborted: bool,
+}
+
+impl<'a> Search<'a> {
+    /// Search without time limit
+    pub(super) fn new(config: &'a EngineConfig, table: &'a mut TranspositionTable) -> Self {
+        Search {
+            config,
+            table,
+            root_depth: 0,
+            symmetric: is_symmetric(config),
+            goal: config.target,
+            deadline: None,
+            node_count: 0,
+            aborted: false,
+        }
+    }
+
+    /// Search which is aborted after deadline
+    pub(super) fn with_deadline(
+        config: &'a EngineConfig,
+        table: &'a mut TranspositionTable,
+        deadline: Instant,
+    ) -> Self {
+        Search {
+            deadline: Some(deadline),
+            ..Search::new(config, table)
+        }
+    }
+
+    /// Check deadline, after timeout all nodes become leaves
+    pub(super) fn is_timeout(&mut self) -> bool {
+        if self.aborted {
+            return true;
+        }
+
+        if let Some(deadline) = self.deadline {
+            self.node_count += 1;
+            if self.node_count & (CHECK_PERIOD - 1) == 0 && Instant::now() >= deadline {
+                self.aborted = true;
+            }
+        }
+        self.aborted
+    }
+
+    /// Result of aborted search is incomplete
+    pub(super) fn is_aborted(&self) -> bool {
+        self.aborted
+    }
+}
+
+/// Symmetric positions have the same score if evaluation and all random moves are symmetric,
+/// limited random moves take the first empty cells
+fn is_symmetric(config: &EngineConfig) -> bool {
+    config.transposition_size > 0
+        && config.eval_fn.is_symmetric()
+        && matches!(config.random_mode, RandomCompleteness::Full)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::engine_config::Algorithm;
+    use crate::engine::evaluation::Weights;
+
+    fn config() -> EngineConfig {
+        let weights = Weights {
+            max_cell: 1,
+            max_score: 0,
+            monotonicity: 0,
+            smoothness: 0,
+            std_dev: 0,
+            free_space: 0,
+            snakeiness: 0,
+        };
+        EngineConfig {
+            order_moves: false,
+            ..EngineConfig::new(1, weights, Algorithm::ExpectiMinimax)
+        }
+    }
+
+    #[test]
+    fn should_abort_after_deadline() {
+        let config = config();
+        let mut table = TranspositionTable::new(config.transposition_size);
+        let mut search = Search::with_deadline(&config, &mut table, Instant::now());
+        for _ in 1..CHECK_PERIOD {
+            assert!(!search.is_timeout());
+        }
+        assert!(search.is_timeout());
+        assert!(search.is_aborted());
+
+        let mut search = Search::new(&config, &mut table);
+        for _ in 0..CHECK_PERIOD * 2 {
+            assert!(!search.is_timeout());
+        }
+    }
+
+    #[test]
+    fn should_share_symmetric_entries_only_with_full_random_moves() {
+        let mut config = config();
+        let mut table = TranspositionTable::new(0);
+        assert!(!Search::new(&config, &mut table).symmetric);
+
+        config.transposition_size = 1 << 10;
+        assert!(Search::new(&config, &mut table).symmetric);
+
+        config.random_mode = RandomCompleteness::Ordered(2);
+        assert!(!Search::new(&config, &mut table).symmetric);
+        config.random_mode = RandomCompleteness::MonteCarlo(2);
+        assert!(!Search::new(&config, &mut table).symmetric);
+
+        config.random_mode = RandomCompleteness::Full;
+        config.eval_fn.snakeiness = 1;
+        assert!(!Search::new(&config, &mut table).symmetric);
+    }
+}
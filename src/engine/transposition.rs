@@ -0,0 +1,174 @@
+/* transposition.rs -- transposition table.
+Copyright (C) 2021 fuggy
+
+This file is part of game-2048-engine.
+
+game-2048-engine is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+game-2048-engine is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with game-2048-engine.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+/// Kind of stored score relative to the search window
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(super) enum Bound {
+    /// Score inside window
+    Exact,
+    /// Fail high, real score is greater or equal
+    Lower,
+    /// Fail low, real score is less or equal
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    board_id: u64,
+    /// position after human move, random move is next
+    random_turn: bool,
+    score: i32,
+    depth: u16,
+    bound: Bound,
+}
+
+/// Fixed size table of searched positions, newer entries replace older
+pub(super) struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+    mask: usize,
+}
+
+impl TranspositionTable {
+    /// Create table, size is rounded down to a power of two, 0 - disabled
+    pub(super) fn new(size: usize) -> Self {
+        let size = if size == 0 {
+            0
+        } else {
+            1 << (usize::BITS - 1 - size.leading_zeros())
+        };
+        TranspositionTable {
+            entries: vec![None; size],
+            mask: size.wrapping_sub(1),
+        }
+    }
+
+    fn index(&self, board_id: u64, random_turn: bool) -> usize {
+        //fibonacci hashing, board id has poor low bits
+        let hash = (board_id ^ random_turn as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        (hash >> 32) as usize & self.mask
+    }
+
+    /// Find score of the position searched at least to depth
+    /// which is usable with (alpha, beta) window
+    pub(super) fn probe(
+        &self,
+        board_id: u64,
+        random_turn: bool,
+        depth: u16,
+        alpha: i32,
+        beta: i32,
+    ) -> Option<i32> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        let entry = self.entries[self.index(board_id, random_turn)]?;
+        if entry.board_id != board_id || entry.random_turn != random_turn || entry.depth < depth {
+            return None;
+        }
+
+        match entry.bound {
+            Bound::Exact => Some(entry.score),
+            Bound::Lower if entry.score >= beta => Some(entry.score),
+            Bound::Upper if entry.score <= alpha => Some(entry.score),
+            _ => None,
+        }
+    }
+
+    /// Save score of the searched position
+    pub(super) fn store(
+        &mut self,
+        board_id: u64,
+        random_turn: bool,
+        depth: u16,
+        score: i32,
+        bound: Bound,
+    ) {
+        if self.entries.is_empty() {
+            return;
+        }
+
+        let index = self.index(board_id, random_turn);
+        self.entries[index] = Some(Entry {
+            board_id,
+            random_turn,
+            score,
+            depth,
+            bound,
+        });
+    }
+}
+
+/// Bound of fail-soft score for (alpha, beta) window
+pub(super) fn bound_of(score: i32, alpha: i32, beta: i32) -> Bound {
+    if score <= alpha {
+        Bound::Upper
+    } else if score >= beta {
+        Bound::Lower
+    } else {
+        Bound::Exact
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_round_size() {
+        assert_eq!(TranspositionTable::new(0).entries.len(), 0);
+        assert_eq!(TranspositionTable::new(1).entries.len(), 1);
+        assert_eq!(TranspositionTable::new(1000).entries.len(), 512);
+        assert_eq!(TranspositionTable::new(1024).entries.len(), 1024);
+    }
+
+    #[test]
+    fn should_probe_exact() {
+        let mut table = TranspositionTable::new(1024);
+        table.store(0x1234, false, 3, 100, Bound::Exact);
+
+        assert_eq!(table.probe(0x1234, false, 3, 0, 10), Some(100));
+        assert_eq!(table.probe(0x1234, false, 2, 0, 10), Some(100));
+        // not deep enough
+        assert_eq!(table.probe(0x1234, false, 4, 0, 10), None);
+        // another player to move
+        assert_eq!(table.probe(0x1234, true, 3, 0, 10), None);
+        assert_eq!(table.probe(0x4321, false, 3, 0, 10), None);
+    }
+
+    #[test]
+    fn should_probe_bounds() {
+        let mut table = TranspositionTable::new(1024);
+        table.store(1, false, 3, 100, Bound::Lower);
+        table.store(2, false, 3, 100, Bound::Upper);
+
+        assert_eq!(table.probe(1, false, 3, 0, 50), Some(100));
+        assert_eq!(table.probe(1, false, 3, 0, 200), None);
+        assert_eq!(table.probe(2, false, 3, 150, 200), Some(100));
+        assert_eq!(table.probe(2, false, 3, 0, 200), None);
+    }
+
+    #[test]
+    fn should_ignore_disabled() {
+        let mut table = TranspositionTable::new(0);
+        table.store(1, false, 3, 100, Bound::Exact);
+
+        assert_eq!(table.probe(1, false, 3, 0, 50), None);
+    }
+}
@@ -17,56 +17,219 @@ You should have received a copy of the GNU General Public License
 along with game-2048-engine.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use crate::board;
-use crate::board::{Board, State};
+use crate::board::{Board, State, BOARD_SIZE};
 use crate::direction::Direction;
+use crate::engine::adversary::EvilSpawner;
+use crate::engine::moves::Move;
 use crate::random;
-use crate::random::{Rnd, RndMove};
+use crate::random::{RndMove, SpawnError, SpawnRules, Xoshiro256};
+use crate::record::{Record, Step};
+use std::collections::VecDeque;
 
-pub struct Game {
-    pub board: Board,
-    rnd: Rnd,
+/// Undo steps kept by default, a step is a human or a random move
+pub const DEFAULT_HISTORY_LIMIT: usize = 1024;
+/// Tile winning the original game
+pub const DEFAULT_TARGET: u64 = 2048;
+
+/// Cloned game continues with the same random moves
+#[derive(Clone)]
+pub struct Game<const N: usize = BOARD_SIZE> {
+    pub board: Board<N>,
+    rnd: Box<dyn RndMove>,
+    rules: SpawnRules,
+    spawner: Spawner,
+    history: History<N>,
+    /// tile winning the game, none - the game is played until losing
+    target: Option<u64>,
+    /// moves are allowed after reaching target
+    keep_playing: bool,
+    /// move count when target was reached
+    won_at: Option<u16>,
+    recording: Option<Recording<N>>,
 }
 
-impl Game {
-    /// Create new start position defined by seed
-    pub fn with_seed(seed: u32) -> Self {
-        let mut start_position = Game {
-            board: Board::new(),
-            rnd: Rnd::with_seed(seed),
+/// Who chooses random moves
+#[derive(Clone, Copy)]
+pub enum Spawner {
+    /// Random generator with spawn rules
+    Random,
+    /// The worst values of spawn rules for the player
+    Evil(EvilSpawner),
+}
+
+/// Everything changed by moves, steps of recording are shared
+#[derive(Clone)]
+struct Position<const N: usize> {
+    board: Board<N>,
+    rnd: Box<dyn RndMove>,
+    won_at: Option<u16>,
+    record_len: usize,
+}
+
+/// Steps from the start position, the ones after 'len' are undone
+#[derive(Clone)]
+struct Recording<const N: usize> {
+    seed: Option<u64>,
+    start: Board<N>,
+    steps: Vec<Step>,
+    len: usize,
+}
+
+impl<const N: usize> Recording<N> {
+    fn new(seed: Option<u64>, start: Board<N>) -> Self {
+        Recording {
+            seed,
+            start,
+            steps: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// New step forgets undone ones
+    fn push(&mut self, turn: Move, score: u32) {
+        self.steps.truncate(self.len);
+        self.steps.push(Step { turn, score });
+        self.len += 1;
+    }
+}
+
+/// Positions before made moves and after undone moves
+#[derive(Clone)]
+struct History<const N: usize> {
+    undo: VecDeque<Position<N>>,
+    redo: Vec<Position<N>>,
+    limit: usize,
+}
+
+/// Saved game position with the random generator state and recorded moves
+#[derive(Clone)]
+pub struct Snapshot<const N: usize> {
+    position: Position<N>,
+    steps: Vec<Step>,
+}
+
+impl<const N: usize> Snapshot<N> {
+    pub fn board(&self) -> &Board<N> {
+        &self.position.board
+    }
+}
+
+impl<const N: usize> History<N> {
+    fn with_limit(limit: usize) -> Self {
+        History {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            limit,
+        }
+    }
+
+    /// Save position before a new move, undone moves are forgotten
+    fn push(&mut self, position: Option<Position<N>>) {
+        self.redo.clear();
+        let position = match position {
+            Some(position) if self.limit > 0 => position,
+            _ => return,
         };
+        if self.undo.len() == self.limit {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(position);
+    }
+}
 
-        start_position.init_new();
-        start_position
+impl<const N: usize> Game<N> {
+    /// Create new start position defined by seed
+    pub fn with_seed(seed: u64) -> Self {
+        let mut game = Game::with_rnd(Xoshiro256::with_seed(seed));
+        game.recording = Some(Recording::new(Some(seed), game.board));
+        game
     }
 
     /// Create new start position
     pub fn start_new() -> Self {
+        Game::with_rnd(random::get_rnd())
+    }
+
+    /// Create new start position with own generator, e.g. 'Lcg' for old games
+    pub fn with_rnd<R: RndMove + 'static>(rnd: R) -> Self {
+        Game::new_game(Box::new(rnd), SpawnRules::default())
+    }
+
+    /// Create new start position of the game variant
+    pub fn with_rules<R: RndMove + 'static>(rnd: R, rules: SpawnRules) -> Result<Self, SpawnError> {
+        rules.validate::<N>()?;
+        Ok(Game::new_game(Box::new(rnd), rules))
+    }
+
+    fn new_game(rnd: Box<dyn RndMove>, rules: SpawnRules) -> Self {
         let mut start_position = Game {
             board: Board::new(),
-            rnd: random::get_rnd(),
+            rnd,
+            rules,
+            spawner: Spawner::Random,
+            history: History::with_limit(DEFAULT_HISTORY_LIMIT),
+            target: None,
+            keep_playing: false,
+            won_at: None,
+            recording: None,
         };
 
         start_position.init_new();
+        start_position.recording = Some(Recording::new(None, start_position.board));
         start_position
     }
 
-    fn init_new(&mut self) {
-        const CELL_COUNT: u8 = (board::BOARD_SIZE * board::BOARD_SIZE) as u8;
-        // 'CELL_COUNT' empty cell at the beginning
-        let next_move = self.rnd.next_move(CELL_COUNT);
-        self.board.set_move(next_move);
+    /// Continue game from given position, next random moves are defined by seed
+    pub fn with_board(board: Board<N>, seed: u64) -> Self {
+        let mut game = Game::with_seed(seed);
+        game.board = board;
+        game.recording = Some(Recording::new(Some(seed), board));
+        game.check_target();
+        game
+    }
 
-        let next_move = self.rnd.next_move(CELL_COUNT - 1);
-        self.board.set_move(next_move);
+    /// Continue game from position with own random sequence
+    /// until losing, without history
+    pub(super) fn from_board<R: RndMove + 'static>(
+        board: Board<N>,
+        rules: SpawnRules,
+        rnd: R,
+    ) -> Self {
+        Game {
+            board,
+            rnd: Box::new(rnd),
+            rules,
+            spawner: Spawner::Random,
+            history: History::with_limit(0),
+            target: None,
+            keep_playing: true,
+            won_at: None,
+            recording: None,
+        }
+    }
+
+    fn init_new(&mut self) {
+        let cell_count = (N * N) as u8;
+        // 'cell_count' empty cell at the beginning
+        for empty_count in (1..=cell_count)
+            .rev()
+            .take(self.rules.starting_tiles as usize)
+        {
+            let next_move = self.rules.next_move(self.rnd.as_mut(), empty_count);
+            self.board.set_move(next_move);
+        }
         self.board.move_count = 0;
     }
 
-    /// Make human move then random move
+    /// Make human move then random moves of the turn
     pub fn make_move(&mut self, dir: Direction) -> bool {
         let move_made = self.human_move(dir);
         if move_made {
-            self.random_move();
+            for _ in 0..self.rules.tiles_per_turn {
+                if self.random_move().is_none() {
+                    break;
+                }
+            }
         }
         move_made
     }
@@ -74,7 +237,7 @@ impl Game {
     /// Make human move
     /// returns: change has been made
     pub fn human_move(&mut self, dir: Direction) -> bool {
-        if self.board.state == State::Lose {
+        if self.board.state != State::InGame {
             return false;
         }
 
@@ -83,24 +246,216 @@ impl Game {
             return false;
         }
 
-        self.board.slide_to(dir)
+        let board = self.board;
+        let moved = self.board.slide_to(dir);
+        if moved {
+            //only the board is changed yet
+            let before = self
+                .history_position()
+                .map(|position| Position { board, ..position });
+            self.history.push(before);
+            self.record_step(Move::Human(dir));
+            self.check_target();
+        }
+        moved
     }
 
-    /// Put random value in an empty spot
+    fn record_step(&mut self, turn: Move) {
+        if let Some(recording) = self.recording.as_mut() {
+            recording.push(turn, self.board.score);
+        }
+    }
+
+    /// The first time target is reached the game is won
+    fn check_target(&mut self) {
+        let reached = self
+            .target
+            .is_some_and(|target| self.board.max_cell() >= target);
+        if self.won_at.is_none() && reached {
+            self.won_at = Some(self.board.move_count);
+            if !self.keep_playing {
+                self.board.state = State::Win;
+            }
+        }
+    }
+
+    /// Put random value in an empty spot, the winning move is followed by it too
     pub fn random_move(&mut self) -> Option<(u8, u8)> {
-        if self.board.state == State::Lose {
+        let empty_count = self.board.empty_count();
+        if self.board.state == State::Lose || empty_count == 0 {
             return None;
         }
 
-        let empty_count = self.board.empty_count();
-        let next_move = self.rnd.next_move(empty_count);
+        let before = self.history_position();
+        let next_move = match self.spawner {
+            Spawner::Random => self.rules.next_move(self.rnd.as_mut(), empty_count),
+            Spawner::Evil(evil) => evil.choose(&self.board, &self.rules)?,
+        };
+        self.put_random_move(before, next_move);
+        Some(next_move)
+    }
+
+    /// Put given value instead of generated one, e.g. a spawn of another game
+    /// returns: false if the game is lost or there is no such empty cell
+    pub fn set_random_move(&mut self, next_move: (u8, u8)) -> bool {
+        let (value, pos) = next_move;
+        if self.board.state == State::Lose || value == 0 || pos >= self.board.empty_count() {
+            return false;
+        }
+        self.put_random_move(self.history_position(), next_move);
+        true
+    }
+
+    fn put_random_move(&mut self, before: Option<Position<N>>, next_move: (u8, u8)) {
+        self.history.push(before);
         self.board.set_move(next_move);
+        let (value, pos) = next_move;
+        self.record_step(Move::Random(value, pos));
 
-        if !self.board.can_move() {
+        if self.board.state == State::InGame && !self.board.can_move() {
             self.board.state = State::Lose;
         }
+    }
 
-        Some(next_move)
+    /// Moves from the start position, None for games without recording
+    pub fn record(&self) -> Option<Record<N>> {
+        self.recording.as_ref().map(|recording| Record {
+            seed: recording.seed,
+            start: recording.start,
+            steps: recording.steps[..recording.len].to_vec(),
+            end: self.board,
+        })
+    }
+
+    /// Take back the last human or random move, the same random move follows again
+    /// returns: false if history is empty
+    pub fn undo(&mut self) -> bool {
+        match self.history.undo.pop_back() {
+            Some(position) => {
+                let current = self.swap_position(position);
+                self.history.redo.push(current);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Make again the last undone move
+    /// returns: false if nothing is undone or a new move was made after undo
+    pub fn redo(&mut self) -> bool {
+        match self.history.redo.pop() {
+            Some(position) => {
+                let current = self.swap_position(position);
+                self.history.undo.push_back(current);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn position(&self) -> Position<N> {
+        Position {
+            board: self.board,
+            rnd: self.rnd.clone(),
+            won_at: self.won_at,
+            record_len: self.recording.as_ref().map_or(0, |recording| recording.len),
+        }
+    }
+
+    /// Position before a move, none without history
+    fn history_position(&self) -> Option<Position<N>> {
+        if self.history.limit == 0 {
+            None
+        } else {
+            Some(self.position())
+        }
+    }
+
+    /// Return to the position of history
+    /// returns: replaced current position
+    fn swap_position(&mut self, position: Position<N>) -> Position<N> {
+        let current = self.position();
+        self.board = position.board;
+        self.rnd = position.rnd;
+        self.won_at = position.won_at;
+        if let Some(recording) = self.recording.as_mut() {
+            recording.len = position.record_len;
+        }
+        if self.keep_playing && self.board.state == State::Win {
+            self.board.state = State::InGame;
+        }
+        current
+    }
+
+    /// Save the position to come back with 'restore'
+    pub fn snapshot(&self) -> Snapshot<N> {
+        let steps = match self.recording {
+            Some(ref recording) => recording.steps[..recording.len].to_vec(),
+            None => Vec::new(),
+        };
+        Snapshot {
+            position: self.position(),
+            steps,
+        }
+    }
+
+    /// Return to the saved position, the next random moves are the same as after saving.
+    /// Undo history is cleared
+    pub fn restore(&mut self, snapshot: &Snapshot<N>) {
+        self.swap_position(snapshot.position.clone());
+        if let Some(recording) = self.recording.as_mut() {
+            recording.steps = snapshot.steps.clone();
+            recording.len = recording.steps.len();
+        }
+        self.history.undo.clear();
+        self.history.redo.clear();
+    }
+
+    /// Independent copy to try other moves with the same randomness
+    pub fn fork(&self) -> Game<N> {
+        self.clone()
+    }
+
+    /// Choose random moves of the game from now on, start position is always random
+    pub fn set_spawner(&mut self, spawner: Spawner) {
+        self.spawner = spawner;
+    }
+
+    /// Tile winning the game, e.g. 'DEFAULT_TARGET', there is no target by default
+    pub fn set_target(&mut self, target: u64) {
+        self.target = Some(target);
+        self.check_target();
+    }
+
+    /// Continue the won game, or don't stop at target at all
+    pub fn keep_playing(&mut self) {
+        self.keep_playing = true;
+        if self.board.state == State::Win {
+            self.board.state = if self.board.can_move() {
+                State::InGame
+            } else {
+                State::Lose
+            };
+        }
+    }
+
+    /// The number of moves made to reach target
+    pub fn won_at(&self) -> Option<u16> {
+        self.won_at
+    }
+
+    /// Spawns of random moves
+    pub fn rules(&self) -> &SpawnRules {
+        &self.rules
+    }
+
+    /// Keep at most 'limit' moves for undo, 0 disables history
+    pub fn set_history_limit(&mut self, limit: usize) {
+        let history = &mut self.history;
+        history.limit = limit;
+        while history.undo.len() > limit {
+            history.undo.pop_front();
+        }
     }
 
     /// Count the number of empty cells
@@ -109,7 +464,7 @@ impl Game {
     }
 
     /// The value of the maximum cell
-    pub fn max_cell(&self) -> u16 {
+    pub fn max_cell(&self) -> u64 {
         self.board.max_cell()
     }
 }
@@ -118,25 +473,30 @@ impl Game {
 mod tests {
     use super::*;
     use crate::board;
+    use crate::engine::evaluation::Weights;
+    use crate::random::{Lcg, SpawnValues};
+    use crate::symmetry::Symmetry;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
 
     #[test]
     #[rustfmt::skip]
     fn should_can_play() {
         let mut game = Game::start_new();
-        game.board.board =
+        game.board.set_cells(
            [[1, 2, 1, 2],
             [2, 1, 2, 1],
             [1, 0, 1, 2],
-            [2, 1, 2, 1]];
+            [2, 1, 2, 1]]);
 
         assert!(game.board.can_move());
 
         let mut game = Game::start_new();
-        game.board.board =
+        game.board.set_cells(
            [[3, 2, 1, 1],
             [1, 1, 2, 2],
             [3, 2, 1, 1],
-            [2, 1, 2, 1]];
+            [2, 1, 2, 1]]);
 
         assert!(game.board.can_move());
     }
@@ -145,11 +505,11 @@ mod tests {
     #[rustfmt::skip]
     fn should_lose_game() {
         let mut game = Game::start_new();
-        game.board.board =
+        game.board.set_cells(
            [[1, 2, 1, 2],
             [2, 1, 2, 1],
             [1, 2, 1, 2],
-            [2, 1, 2, 1]];
+            [2, 1, 2, 1]]);
 
         assert!(!game.board.can_move());
     }
@@ -159,11 +519,11 @@ mod tests {
     fn should_slide_left_with_zero_start() {
         board::load_cache();
         let mut game = Game::start_new();
-        game.board.board =
+        game.board.set_cells(
            [[1, 2, 1, 2],
             [0, 1, 2, 1],
             [1, 2, 1, 2],
-            [2, 1, 2, 1]];
+            [2, 1, 2, 1]]);
 
         let moved = game.human_move(Direction::Left);
         assert!(moved);
@@ -174,11 +534,11 @@ mod tests {
     fn should_slide_left_with_zero_end() {
         board::load_cache();
         let mut game = Game::start_new();
-        game.board.board =
+        game.board.set_cells(
            [[1, 2, 1, 2],
             [2, 1, 2, 0],
             [1, 2, 1, 2],
-            [2, 1, 2, 1]];
+            [2, 1, 2, 1]]);
 
         let moved = game.human_move(Direction::Left);
         assert!(!moved);
@@ -189,11 +549,11 @@ mod tests {
     fn should_slide_left_with_merge() {
         board::load_cache();
         let mut game = Game::start_new();
-        game.board.board =
+        game.board.set_cells(
            [[3, 2, 1, 1],
             [1, 1, 2, 2],
             [3, 2, 1, 1],
-            [2, 1, 2, 1]];
+            [2, 1, 2, 1]]);
 
         let moved = game.human_move(Direction::Left);
         assert!(moved);
@@ -204,11 +564,11 @@ mod tests {
     fn should_slide_right_with_zero_start() {
         board::load_cache();
         let mut game = Game::start_new();
-        game.board.board =
+        game.board.set_cells(
            [[1, 2, 1, 2],
             [2, 1, 2, 0],
             [1, 2, 1, 2],
-            [2, 1, 2, 1]];
+            [2, 1, 2, 1]]);
 
         let moved = game.human_move(Direction::Right);
         assert!(moved);
@@ -219,11 +579,11 @@ mod tests {
     fn should_slide_right_with_zero_end() {
         board::load_cache();
         let mut game = Game::start_new();
-        game.board.board =
+        game.board.set_cells(
            [[1, 2, 1, 2],
             [0, 1, 2, 1],
             [1, 2, 1, 2],
-            [2, 1, 2, 1]];
+            [2, 1, 2, 1]]);
 
         let moved = game.human_move(Direction::Right);
         assert!(!moved);
@@ -234,11 +594,11 @@ mod tests {
     fn should_slide_right_with_merge() {
         board::load_cache();
         let mut game = Game::start_new();
-        game.board.board =
+        game.board.set_cells(
            [[3, 2, 1, 1],
             [1, 1, 2, 2],
             [3, 2, 1, 1],
-            [2, 1, 2, 1]];
+            [2, 1, 2, 1]]);
 
         let moved = game.human_move(Direction::Right);
         assert!(moved);
@@ -249,11 +609,11 @@ mod tests {
     fn should_slide_up_with_zero_start() {
         board::load_cache();
         let mut game = Game::start_new();
-        game.board.board =
+        game.board.set_cells(
            [[1, 2, 0, 2],
             [2, 1, 2, 1],
             [1, 2, 1, 2],
-            [2, 1, 2, 1]];
+            [2, 1, 2, 1]]);
 
         let moved = game.human_move(Direction::Up);
         assert!(moved);
@@ -264,11 +624,11 @@ mod tests {
     fn should_slide_up_with_zero_end() {
         board::load_cache();
         let mut game = Game::start_new();
-        game.board.board =
+        game.board.set_cells(
            [[1, 2, 1, 2],
             [2, 1, 2, 1],
             [1, 2, 1, 2],
-            [2, 1, 0, 1]];
+            [2, 1, 0, 1]]);
 
         let moved = game.human_move(Direction::Up);
         assert!(!moved);
@@ -279,11 +639,11 @@ mod tests {
     fn should_slide_up_with_merge() {
         board::load_cache();
         let mut game = Game::start_new();
-        game.board.board =
+        game.board.set_cells(
            [[1, 3, 2, 3],
             [2, 1, 2, 1],
             [1, 2, 1, 2],
-            [2, 1, 2, 1]];
+            [2, 1, 2, 1]]);
 
         let moved = game.human_move(Direction::Up);
         assert!(moved);
@@ -294,11 +654,11 @@ mod tests {
     fn should_slide_down_with_zero_start() {
         board::load_cache();
         let mut game = Game::start_new();
-        game.board.board =
+        game.board.set_cells(
            [[1, 2, 1, 2],
             [2, 1, 2, 1],
             [1, 2, 1, 2],
-            [2, 1, 0, 1]];
+            [2, 1, 0, 1]]);
 
         let moved = game.human_move(Direction::Down);
         assert!(moved);
@@ -309,11 +669,11 @@ mod tests {
     fn should_slide_down_with_zero_end() {
         board::load_cache();
         let mut game = Game::start_new();
-        game.board.board =
+        game.board.set_cells(
            [[1, 2, 0, 2],
             [2, 1, 2, 1],
             [1, 2, 1, 2],
-            [2, 1, 2, 1]];
+            [2, 1, 2, 1]]);
 
         let moved = game.human_move(Direction::Down);
         assert!(!moved);
@@ -324,11 +684,11 @@ mod tests {
     fn should_slide_down_with_merge() {
         board::load_cache();
         let mut game = Game::start_new();
-        game.board.board =
+        game.board.set_cells(
            [[1, 2, 1, 2],
             [2, 1, 2, 1],
             [1, 2, 1, 2],
-            [2, 3, 1, 3]];
+            [2, 3, 1, 3]]);
 
         let moved = game.human_move(Direction::Down);
         assert!(moved);
@@ -339,13 +699,449 @@ mod tests {
     fn should_slide_down_with_merge2() {
         board::load_cache();
         let mut game = Game::start_new();
-        game.board.board =
+        game.board.set_cells(
            [[3, 4, 2, 1],
             [3, 6, 4, 3],
             [4, 8, 6, 4],
-            [1, 3, 7, 1]];
+            [1, 3, 7, 1]]);
 
         let moved = game.human_move(Direction::Down);
         assert!(moved);
     }
+
+    #[test]
+    #[rustfmt::skip]
+    fn should_merge_tiles_above_32768() {
+        board::load_cache();
+        let mut game = Game::start_new();
+        game.board.set_cells(
+           [[15, 15, 16, 16],
+            [16, 0, 16, 0],
+            [1, 2, 3, 4],
+            [0, 0, 0, 1]]);
+        game.board.score = 0;
+
+        assert!(game.human_move(Direction::Left));
+        assert_eq!(game.board.cells(),
+           [[16, 17, 0, 0],
+            [17, 0, 0, 0],
+            [1, 2, 3, 4],
+            [1, 0, 0, 0]]);
+        assert_eq!(game.board.score, 65536 + 2 * 131072);
+        assert_eq!(game.max_cell(), 131072);
+
+        let mut other = game.board;
+        other.set_cell(3, 3, 16);
+        assert_ne!(other.get_board_id(), game.board.get_board_id());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn should_move_only_big_tiles() {
+        board::load_cache();
+        let mut game = Game::start_new();
+        game.board.set_cells(
+           [[16, 16, 1, 2],
+            [2, 1, 2, 1],
+            [1, 2, 1, 2],
+            [2, 1, 2, 1]]);
+
+        assert!(game.board.can_move());
+        assert!(!game.human_move(Direction::Up));
+        assert!(game.human_move(Direction::Right));
+        assert_eq!(game.board.cells()[0], [0, 17, 1, 2]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn should_slide_other_sizes() {
+        let mut game: Game<3> = Game::with_seed(3);
+        game.board.set_cells(
+           [[1, 0, 1],
+            [0, 2, 2],
+            [3, 0, 0]]);
+
+        assert!(game.human_move(Direction::Right));
+        assert_eq!(game.board.cells(),
+           [[0, 0, 2],
+            [0, 0, 3],
+            [0, 0, 3]]);
+        assert_eq!(game.board.score, 4 + 8);
+
+        let mut game: Game<5> = Game::with_seed(3);
+        game.board.set_cells(
+           [[1, 0, 0, 0, 0],
+            [1, 0, 0, 0, 0],
+            [2, 0, 0, 0, 1],
+            [0, 0, 0, 0, 1],
+            [0, 0, 0, 0, 3]]);
+
+        assert!(game.human_move(Direction::Up));
+        assert_eq!(game.board.cells(),
+           [[2, 0, 0, 0, 2],
+            [2, 0, 0, 0, 3],
+            [0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0]]);
+        assert_eq!(game.board.score, 4 + 4);
+    }
+
+    #[test]
+    fn should_play_other_sizes_to_the_end() {
+        let mut game: Game<3> = Game::with_seed(5);
+        assert_eq!(game.empty_count(), 7);
+        while [
+            Direction::Left,
+            Direction::Down,
+            Direction::Right,
+            Direction::Up,
+        ]
+        .iter()
+        .any(|&dir| game.make_move(dir))
+        {}
+        assert_eq!(game.board.state, State::Lose);
+
+        let game: Game<6> = Game::with_seed(5);
+        assert_eq!(game.empty_count(), 34);
+    }
+
+    #[test]
+    fn should_replay_old_lcg_game() {
+        let mut game: Game = Game::with_rnd(Lcg::with_seed(3));
+        assert_eq!(game.board.get_board_id(), 0x0100_0000_0100_0000);
+        game.make_move(Direction::Left);
+        assert_eq!(game.board.get_board_id(), 0x1000_0000_1010_0000);
+    }
+
+    #[test]
+    fn should_spawn_by_rules() {
+        let rules = SpawnRules {
+            values: SpawnValues::Weighted(vec![(3, 1)]),
+            tiles_per_turn: 2,
+            starting_tiles: 3,
+        };
+        let mut game: Game = Game::with_rules(Xoshiro256::with_seed(3), rules).unwrap();
+        assert_eq!(game.empty_count(), 13);
+        assert_eq!(game.max_cell(), 8);
+
+        assert!(game.make_move(Direction::Left) || game.make_move(Direction::Right));
+        assert_eq!(game.board.move_count, 3);
+        assert!(game
+            .board
+            .tiles()
+            .iter()
+            .flatten()
+            .all(|&v| v == 0 || v >= 8));
+
+        //all cells are filled
+        let rules = SpawnRules {
+            starting_tiles: 20,
+            ..SpawnRules::default()
+        };
+        let game: Game = Game::with_rules(Xoshiro256::with_seed(3), rules).unwrap();
+        assert_eq!(game.empty_count(), 0);
+    }
+
+    #[test]
+    fn should_reject_rules_without_tiles() {
+        let weighted = |weights: Vec<(u8, u32)>| {
+            let rules = SpawnRules {
+                values: SpawnValues::Weighted(weights),
+                ..SpawnRules::default()
+            };
+            Game::<4>::with_rules(Xoshiro256::with_seed(3), rules).map(|game| game.max_cell())
+        };
+        assert_eq!(weighted(vec![]).err(), Some(SpawnError::NoValues));
+        assert_eq!(
+            weighted(vec![(1, 0), (2, 0)]).err(),
+            Some(SpawnError::NoValues)
+        );
+        assert_eq!(
+            weighted(vec![(0, 1), (1, 9)]).err(),
+            Some(SpawnError::EmptyValue)
+        );
+        assert_eq!(
+            weighted(vec![(1, u32::MAX), (2, 1)]).err(),
+            Some(SpawnError::WeightOverflow)
+        );
+        assert_eq!(
+            weighted(vec![(1, 1), (18, 1)]).err(),
+            Some(SpawnError::TooBig(18))
+        );
+        assert_eq!(weighted(vec![(64, 1)]).err(), Some(SpawnError::TooBig(64)));
+        assert_eq!(weighted(vec![(1, 0), (2, 1)]), Ok(4));
+        assert_eq!(weighted(vec![(17, 1)]), Ok(131_072));
+    }
+
+    #[test]
+    fn should_spawn_the_worst_tile() {
+        let board: Board = "2 4 2 4\n4 2 4 2\n2 4 2 4\n4 2 4 .".parse().unwrap();
+        let mut game = Game::with_board(board, 3);
+        game.set_spawner(Spawner::Evil(EvilSpawner {
+            eval_fn: Weights {
+                max_cell: 0,
+                max_score: 0,
+                monotonicity: 0,
+                smoothness: 0,
+                std_dev: 0,
+                free_space: 1,
+                snakeiness: 0,
+            },
+            plies: 1,
+        }));
+
+        assert_eq!(game.random_move(), Some((1, 0)));
+        assert_eq!(game.board.state, State::Lose);
+    }
+
+    #[test]
+    fn should_fork_with_same_random_moves() {
+        let mut game: Game = Game::with_seed(7);
+        let mut fork = game.fork();
+        assert!(fork.make_move(Direction::Left) || fork.make_move(Direction::Right));
+        assert!(game.make_move(Direction::Up) || game.make_move(Direction::Down));
+        assert_ne!(fork.board.get_board_id(), game.board.get_board_id());
+
+        let mut other = fork.fork();
+        for dir in [Direction::Down, Direction::Right, Direction::Up] {
+            assert_eq!(fork.make_move(dir), other.make_move(dir));
+            assert_eq!(fork.board.get_board_id(), other.board.get_board_id());
+        }
+        assert!(other.undo());
+        assert!(!game.redo());
+    }
+
+    #[test]
+    fn should_restore_snapshot() {
+        let mut game: Game = Game::with_seed(7);
+        game.make_move(Direction::Left);
+        let snapshot = game.snapshot();
+        let mut expected = Vec::new();
+        for dir in [Direction::Down, Direction::Right, Direction::Up] {
+            game.make_move(dir);
+            expected.push(game.board.get_board_id());
+        }
+
+        game.restore(&snapshot);
+        assert_eq!(game.board.get_board_id(), snapshot.board().get_board_id());
+        assert!(!game.undo());
+        game.make_move(Direction::Right);
+        game.restore(&snapshot);
+        for (dir, id) in [Direction::Down, Direction::Right, Direction::Up]
+            .iter()
+            .zip(expected)
+        {
+            game.make_move(*dir);
+            assert_eq!(game.board.get_board_id(), id);
+        }
+        assert!(game.undo());
+
+        let record = game.record().unwrap();
+        assert_eq!(record.steps.len(), game.board.move_count as usize);
+        record.replay().verify().unwrap();
+    }
+
+    #[test]
+    fn should_continue_parsed_board() {
+        let board: Board = "0 0 0 0\n0 0 0 0\n0 0 2 0\n0 0 0 2".parse().unwrap();
+        let mut game = Game::with_board(board, 3);
+        assert_eq!(game.board.tiles(), board.tiles());
+        assert!(game.make_move(Direction::Left));
+        assert_eq!(game.empty_count(), 13);
+    }
+
+    #[test]
+    fn should_undo_and_redo_moves() {
+        let mut game: Game = Game::with_seed(3);
+        let start = game.board;
+        assert!(!game.undo());
+
+        assert!(game.make_move(Direction::Left) || game.make_move(Direction::Right));
+        let first = game.board;
+        assert!(game.make_move(Direction::Up) || game.make_move(Direction::Down));
+        let second = game.board;
+
+        assert!(game.undo());
+        assert!(game.undo());
+        assert_eq!(game.board.get_board_id(), first.get_board_id());
+        assert!(game.undo());
+        assert!(game.undo());
+        assert_eq!(game.board.get_board_id(), start.get_board_id());
+        assert_eq!(game.board.move_count, 0);
+        assert!(!game.undo());
+
+        for _ in 0..4 {
+            assert!(game.redo());
+        }
+        assert!(!game.redo());
+        assert_eq!(game.board.get_board_id(), second.get_board_id());
+        assert_eq!(game.board.score, second.score);
+        assert_eq!(game.board.move_count, second.move_count);
+    }
+
+    #[test]
+    fn should_repeat_random_move_after_undo() {
+        let mut game: Game = Game::with_seed(3);
+        assert!(game.human_move(Direction::Left) || game.human_move(Direction::Right));
+        let spawn = game.random_move();
+        let spawned = game.board;
+
+        assert!(game.undo());
+        assert_eq!(game.random_move(), spawn);
+        assert_eq!(game.board.get_board_id(), spawned.get_board_id());
+        //new move forgets undone ones
+        assert!(!game.redo());
+    }
+
+    #[test]
+    fn should_limit_history() {
+        let mut game: Game = Game::with_seed(3);
+        game.set_history_limit(3);
+        for dir in [
+            Direction::Left,
+            Direction::Right,
+            Direction::Up,
+            Direction::Down,
+        ] {
+            game.make_move(dir);
+        }
+        assert!(game.undo());
+        assert!(game.undo());
+        assert!(game.undo());
+        assert!(!game.undo());
+
+        game.set_history_limit(0);
+        assert!(game.make_move(Direction::Left) || game.make_move(Direction::Right));
+        assert!(!game.undo());
+    }
+
+    /// Generator counting its copies
+    #[derive(Debug)]
+    struct CountedRnd(Xoshiro256, Arc<AtomicUsize>);
+
+    impl RndMove for CountedRnd {
+        fn next_u64(&mut self) -> u64 {
+            self.0.next_u64()
+        }
+
+        fn box_clone(&self) -> Box<dyn RndMove> {
+            self.1.fetch_add(1, Ordering::Relaxed);
+            Box::new(CountedRnd(self.0.clone(), self.1.clone()))
+        }
+    }
+
+    #[test]
+    fn should_not_copy_generator_without_history() {
+        let clones = Arc::new(AtomicUsize::new(0));
+        let rnd = CountedRnd(Xoshiro256::with_seed(3), clones.clone());
+        let mut game: Game =
+            Game::from_board(Game::<4>::with_seed(3).board, SpawnRules::default(), rnd);
+        for dir in [Direction::Left, Direction::Right, Direction::Up] {
+            game.make_move(dir);
+        }
+        assert!(game.board.move_count > 0);
+        assert_eq!(clones.load(Ordering::Relaxed), 0);
+
+        game.set_history_limit(2);
+        assert!(game.make_move(Direction::Down) || game.make_move(Direction::Left));
+        assert_eq!(clones.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn should_win_and_keep_playing() {
+        let mut game: Game = Game::with_seed(3);
+        game.set_target(16);
+        game.board.set_cells(
+           [[3, 3, 0, 0],
+            [1, 0, 0, 0],
+            [0, 0, 0, 0],
+            [0, 0, 0, 0]]);
+
+        assert!(game.make_move(Direction::Left));
+        assert_eq!(game.board.state, State::Win);
+        assert_eq!(game.won_at(), Some(1));
+        assert_eq!(game.board.move_count, 2);
+        assert!(!game.make_move(Direction::Right));
+
+        game.keep_playing();
+        assert_eq!(game.board.state, State::InGame);
+        assert!(game.make_move(Direction::Right));
+        assert_eq!(game.won_at(), Some(1));
+
+        assert!(game.undo());
+        assert!(game.undo());
+        assert!(game.undo());
+        assert_eq!(game.board.state, State::InGame);
+        assert_eq!(game.won_at(), Some(1));
+        assert!(game.undo());
+        assert_eq!(game.won_at(), None);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn should_not_win_without_target() {
+        let mut game: Game = Game::with_seed(3);
+        game.board.set_cells(
+           [[10, 10, 0, 0],
+            [1, 0, 0, 0],
+            [0, 0, 0, 0],
+            [0, 0, 0, 0]]);
+
+        assert!(game.make_move(Direction::Left));
+        assert_eq!(game.max_cell(), 2048);
+        assert_eq!(game.board.state, State::InGame);
+        assert_eq!(game.won_at(), None);
+        assert!(game.make_move(Direction::Right));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn should_not_stop_when_keep_playing() {
+        let mut game: Game = Game::with_seed(3);
+        game.set_target(16);
+        game.keep_playing();
+        game.board.set_cells(
+           [[3, 3, 0, 0],
+            [1, 0, 0, 0],
+            [0, 0, 0, 0],
+            [0, 0, 0, 0]]);
+
+        assert!(game.make_move(Direction::Left));
+        assert_eq!(game.board.state, State::InGame);
+        assert_eq!(game.won_at(), Some(1));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn should_slide_symmetric_boards_alike() {
+        let mut game = Game::with_seed(3);
+        game.board.set_cells(
+           [[1, 1, 2, 0],
+            [0, 2, 2, 3],
+            [1, 0, 1, 3],
+            [4, 16, 4, 4]]);
+        let mut small: Game<3> = Game::with_seed(3);
+        small.board.set_cells(
+           [[1, 0, 1],
+            [0, 2, 2],
+            [3, 0, 0]]);
+
+        for sym in Symmetry::ALL {
+            let other = game.board.transform(sym);
+            assert_eq!(other.get_canonical_id(), game.board.get_canonical_id());
+            let (canonical, to_canonical) = other.canonical();
+            assert_eq!(other.transform(to_canonical).cells(), canonical.cells());
+
+            let mut moved = game.board;
+            moved.slide_to(Direction::Left);
+            let mut other_moved = other;
+            other_moved.slide_to(sym.map_direction(Direction::Left));
+            assert_eq!(moved.transform(sym).cells(), other_moved.cells());
+            assert_eq!(moved.score, other_moved.score);
+
+            let other = small.board.transform(sym);
+            assert_eq!(other.get_canonical_id(), small.board.get_canonical_id());
+        }
+    }
 }
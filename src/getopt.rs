@@ -152,7 +152,7 @@ impl Iterator for OptionParser {
                     self.args_index += 1;
                     self.str_index = 0;
                     let str = self.args.get(self.args_index);
-                    self.state = if str == None {
+                    self.state = if str.is_none() {
                         OptFsmState::End
                     } else {
                         OptFsmState::FindOpt
@@ -187,7 +187,7 @@ impl Iterator for OptionParser {
 
                         Some(x) => {
                             self.str_index += 1;
-                            let contains = self.opt_map.get(&x);
+                            let contains = self.opt_map.get(x);
                             match contains {
                                 Some(opt_i) if self.is_required_arg(opt_i) => {
                                     self.optopt = *x;
@@ -322,10 +322,7 @@ mod tests {
 
     #[test]
     fn should_getopt1() {
-        let args: Vec<String> = vec!["./main", "-abcd"]
-            .iter()
-            .map(|x| x.to_string())
-            .collect();
+        let args: Vec<String> = ["./main", "-abcd"].iter().map(|x| x.to_string()).collect();
         let res: Vec<Match> = getopt(args, "abc:", false).collect();
         assert_eq!(
             res[0],
@@ -352,14 +349,14 @@ mod tests {
 
     #[test]
     fn should_getopt2() {
-        let args: Vec<String> = vec!["./main", "-c"].iter().map(|x| x.to_string()).collect();
+        let args: Vec<String> = ["./main", "-c"].iter().map(|x| x.to_string()).collect();
         let res: Vec<Match> = getopt(args, ":abc:", true).collect();
         assert_eq!(res[0], Match::MissingArg { short: 'c' });
     }
 
     #[test]
     fn should_getopt3() {
-        let args: Vec<String> = vec!["./main", "-c"].iter().map(|x| x.to_string()).collect();
+        let args: Vec<String> = ["./main", "-c"].iter().map(|x| x.to_string()).collect();
         let res: Vec<Match> = getopt(args, ":abc::", true).collect();
         assert_eq!(
             res[0],
@@ -372,7 +369,7 @@ mod tests {
 
     #[test]
     fn should_getopt4() {
-        let args: Vec<String> = vec!["./main", "-a", "--", "-b"]
+        let args: Vec<String> = ["./main", "-a", "--", "-b"]
             .iter()
             .map(|x| x.to_string())
             .collect();
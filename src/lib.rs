@@ -17,6 +17,7 @@ You should have received a copy of the GNU General Public License
 along with game-2048-engine.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+pub mod bitboard;
 pub mod board;
 mod cache;
 pub mod direction;
@@ -25,4 +26,6 @@ pub mod game;
 pub mod getopt;
 mod matrix;
 mod output;
-mod random;
+pub mod random;
+pub mod record;
+pub mod symmetry;
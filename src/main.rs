@@ -19,12 +19,14 @@ along with game-2048-engine.  If not, see <https://www.gnu.org/licenses/>.
 
 use game_2048_engine::board::State;
 use game_2048_engine::direction::Direction;
-use game_2048_engine::engine::engine_config::{Algorithm, EngineConfig, RandomCompleteness};
+use game_2048_engine::engine::engine_config::{
+    Algorithm, DepthPolicy, EngineConfig, RandomCompleteness,
+};
 use game_2048_engine::engine::engine_core::Engine;
 use game_2048_engine::engine::evaluation::Weights;
 use game_2048_engine::engine::moves::Move;
-use game_2048_engine::game::Game;
-use game_2048_engine::getopt;
+use game_2048_engine::game::{Game, DEFAULT_TARGET};
+use game_2048_engine::random::SpawnRules;
 use std::convert::TryInto;
 use std::io;
 
@@ -32,8 +34,8 @@ fn main() {
     //load caches
     game_2048_engine::board::load_cache();
 
-    let mut game = Game::with_seed(3);
-    //let mut game = Game::start_new();
+    let mut game: Game = Game::with_seed(3);
+    //let mut game: Game = Game::start_new();
     let weights = Weights {
         max_cell: 30,
         max_score: 10,
@@ -45,12 +47,23 @@ fn main() {
     };
     let engine_config = EngineConfig {
         depth: 7,
+        depth_policy: DepthPolicy::Fixed,
+        //depth_policy: DepthPolicy::Adaptive { min_depth: 3 },
         eval_fn: weights.normalize(),
         algorithm: Algorithm::NegamaxAlphaBeta,
         //algorithm: Algorithm::MinimaxAlphaBeta,
+        //algorithm: Algorithm::NegaScout,
+        //algorithm: Algorithm::ExpectiMinimax,
+        //algorithm: Algorithm::ExpectiMinimaxStar2,
         random_mode: RandomCompleteness::Full,
         //random_mode: RandomCompleteness::MonteCarlo(10),
         order_moves: true,
+        transposition_size: 1 << 20,
+        threads: 1,
+        probability_cutoff: 0.0001,
+        target: None,
+        //target: Some(2048),
+        spawn_rules: SpawnRules::default(),
     };
     let mut engine = Engine::from_game(&game, engine_config);
     loop {
@@ -79,7 +92,8 @@ fn main() {
 
 #[allow(dead_code)]
 fn create_cache() {
-    game_2048_engine::board::create_cache();
+    use game_2048_engine::board;
+    board::create_cache(board::CACHE_FILE).expect("cannot write cache file");
 }
 
 #[allow(dead_code)]
@@ -87,8 +101,9 @@ fn game() {
     //load caches
     game_2048_engine::board::load_cache();
 
-    let mut game = Game::with_seed(3);
-    //let mut game = Game::start_new();
+    let mut game: Game = Game::with_seed(3);
+    //let mut game: Game = Game::start_new();
+    game.set_target(DEFAULT_TARGET);
     loop {
         println!("{}", game);
         let mut input = String::new();
@@ -98,13 +113,31 @@ fn game() {
             .expect("Failed to read line");
 
         if input.len() > 1 {
-            let dir = input.chars().next().unwrap().try_into();
+            let ch = input.chars().next().unwrap();
             print!("\r");
-            if let Ok(value) = dir {
-                game.make_move(value);
+            match ch {
+                //random move then human move
+                'u' => {
+                    game.undo();
+                    game.undo();
+                }
+                'r' => {
+                    game.redo();
+                    game.redo();
+                }
+                _ => {
+                    if let Ok(value) = ch.try_into() {
+                        game.make_move(value);
+                    }
+                }
             }
         }
 
+        if game.board.state == State::Win {
+            println!("{}", game);
+            println!("You won. Moves: {}", game.board.move_count);
+            game.keep_playing();
+        }
         if game.board.state == State::Lose {
             println!("You lost. Score: {}", game.board.score);
             break;
@@ -114,7 +147,7 @@ fn game() {
 
 #[allow(dead_code)]
 fn simple_strategy() {
-    let mut game = Game::start_new();
+    let mut game: Game = Game::start_new();
 
     loop {
         let mut all = false;
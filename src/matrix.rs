@@ -19,10 +19,10 @@ along with game-2048-engine.  If not, see <https://www.gnu.org/licenses/>.
 
 use super::board::BOARD_SIZE;
 
-type Matrix = [[u8; BOARD_SIZE]; BOARD_SIZE];
+type Matrix<const N: usize> = [[u8; N]; N];
 
 /// Max cell value
-pub fn max_cell(m: &Matrix) -> u8 {
+pub fn max_cell<const N: usize>(m: &Matrix<N>) -> u8 {
     let mut max = 0u8;
     for row in m {
         for cell in row {
@@ -35,7 +35,7 @@ pub fn max_cell(m: &Matrix) -> u8 {
 }
 
 /// Count the number of empty cells
-pub fn empty_count(m: &Matrix) -> u8 {
+pub fn empty_count<const N: usize>(m: &Matrix<N>) -> u8 {
     let mut c = 0u8;
     for row in m {
         for cell in row {
@@ -45,48 +45,62 @@ pub fn empty_count(m: &Matrix) -> u8 {
     c
 }
 
-/// Multiply vector by vector
+/// Count the number of different non-empty cells
+pub fn distinct_count<const N: usize>(m: &Matrix<N>) -> u8 {
+    let mut mask = 0u32;
+    for row in m {
+        for cell in row {
+            mask |= 1 << *cell;
+        }
+    }
+    //zero is empty cell
+    (mask & !1).count_ones() as u8
+}
+
+// Multiply vector by vector
 //fn vec_multiply()
 
 /// Sum of absolute value of the difference between pairs
-pub fn monotonicity(m: &Matrix) -> i32 {
+#[allow(clippy::needless_range_loop)]
+pub fn monotonicity<const N: usize>(m: &Matrix<N>) -> i32 {
     let mut c = 0u8;
     //horizontally
     for row in m {
         let mut gt = 0u8;
         let mut eq = 0u8;
-        for i in 0..BOARD_SIZE - 1 {
+        for i in 0..N - 1 {
             gt += (row[i] < row[i + 1]) as u8;
             eq += (row[i] == row[i + 1]) as u8;
         }
 
-        //sum == 3 or sum == 0 or qt = 0
-        //3 increases or 3 decreases or 3 equal
-        c += (((gt + eq) ^ 3) == 0 || gt == 0) as u8;
+        //sum == n - 1 or sum == 0 or qt = 0
+        //n - 1 increases or n - 1 decreases or n - 1 equal
+        c += (gt + eq == N as u8 - 1 || gt == 0) as u8;
     }
 
     //vertically
-    for i in 0..BOARD_SIZE {
+    for i in 0..N {
         let mut gt = 0u8;
         let mut eq = 0u8;
-        for j in 0..BOARD_SIZE - 1 {
+        for j in 0..N - 1 {
             gt += (m[j][i] < m[j + 1][i]) as u8;
             eq += (m[j][i] == m[j + 1][i]) as u8;
         }
 
-        //sum == 3 or sum == 0 or qt = 0
-        //3 increases or 3 decreases or 3 equal
-        c += (((gt + eq) ^ 3) == 0 || gt == 0) as u8;
+        //sum == n - 1 or sum == 0 or qt = 0
+        //n - 1 increases or n - 1 decreases or n - 1 equal
+        c += (gt + eq == N as u8 - 1 || gt == 0) as u8;
     }
     c as i32
 }
 
 /// Sum of absolute value of the difference between pairs
-pub fn smoothness(m: &Matrix) -> i32 {
+#[allow(clippy::needless_range_loop)]
+pub fn smoothness<const N: usize>(m: &Matrix<N>) -> i32 {
     let mut c = 0i16;
     //horizontally
     for row in m {
-        for i in 0..BOARD_SIZE - 1 {
+        for i in 0..N - 1 {
             //abs bit hack =abs(a)
             let a: i8 = row[i] as i8 - row[i + 1] as i8;
             let mask = a >> 7;
@@ -95,8 +109,8 @@ pub fn smoothness(m: &Matrix) -> i32 {
     }
 
     //vertically
-    for j in 0..BOARD_SIZE - 1 {
-        for i in 0..BOARD_SIZE {
+    for j in 0..N - 1 {
+        for i in 0..N {
             //abs bit hack =abs(a)
             let a: i8 = m[j][i] as i8 - m[j + 1][i] as i8;
             let mask = a >> 7;
@@ -106,14 +120,14 @@ pub fn smoothness(m: &Matrix) -> i32 {
     c as i32
 }
 
-pub fn std_dev(m: &Matrix) -> i32 {
-    let mut c = 0u8;
+pub fn std_dev<const N: usize>(m: &Matrix<N>) -> i32 {
+    let mut c = 0u32;
     for row in m {
         for cell in row {
-            c += *cell;
+            c += *cell as u32;
         }
     }
-    let avg = c as f32 / 16.0;
+    let avg = c as f32 / (N * N) as f32;
 
     let mut sd = 0f32;
     for row in m {
@@ -132,23 +146,33 @@ const SNAKE_COEFFICIENTS: [[i32; BOARD_SIZE]; BOARD_SIZE] = [
     [2, 3, 5, 10],
 ];
 
-pub fn snakeiness(m: &Matrix) -> i32 {
+/// Tuned coefficients for default size, other sizes decrease along the snake
+pub fn snake_coefficient<const N: usize>(j: usize, i: usize) -> i32 {
+    if N == BOARD_SIZE {
+        return SNAKE_COEFFICIENTS[j][i];
+    }
+    let i = if j & 1 == 0 { i } else { N - 1 - i };
+    (N * N - (j * N + i)) as i32 * 10
+}
+
+#[allow(clippy::needless_range_loop)]
+pub fn snakeiness<const N: usize>(m: &Matrix<N>) -> i32 {
     let mut c = 0i32;
-    for snake_row in SNAKE_COEFFICIENTS.iter() {
+    for j in 0..N {
         for row in m {
-            c += row
-                .iter()
-                .zip(snake_row.iter())
-                .fold(0i32, |acc, (&a, &b)| acc + a as i32 * b);
+            for i in 0..N {
+                c += row[i] as i32 * snake_coefficient::<N>(j, i);
+            }
         }
     }
     c
 }
 
 /// Transpose the matrix
-pub fn transpose(m: &mut Matrix) {
-    for j in 0..BOARD_SIZE {
-        for i in 0..BOARD_SIZE {
+#[allow(clippy::needless_range_loop)]
+pub fn transpose<const N: usize>(m: &mut Matrix<N>) {
+    for j in 0..N {
+        for i in 0..N {
             if i > j {
                 let tmp = m[j][i];
                 m[j][i] = m[i][j];
@@ -158,17 +182,22 @@ pub fn transpose(m: &mut Matrix) {
     }
 }
 
-#[allow(dead_code)]
 /// Mirror a matrix horizontally
-pub fn mirror_h(m: &mut Matrix) {
-    for j in 0..BOARD_SIZE / 2 {
-        m.swap(j, BOARD_SIZE - 1 - j);
+pub fn mirror_h<const N: usize>(m: &mut Matrix<N>) {
+    for j in 0..N / 2 {
+        m.swap(j, N - 1 - j);
+    }
+}
+
+/// Mirror a matrix vertically
+pub fn mirror_v<const N: usize>(m: &mut Matrix<N>) {
+    for row in m {
+        row.reverse();
     }
 }
 
-#[allow(dead_code)]
-/// Convert to u64 id
-pub fn to_u64(m: &Matrix) -> u64 {
+/// Convert to u64 id, exact up to 4x4
+pub fn to_u64<const N: usize>(m: &Matrix<N>) -> u64 {
     let mut res: u64 = 0;
     for row in m {
         for cell in row {
@@ -178,12 +207,11 @@ pub fn to_u64(m: &Matrix) -> u64 {
     res
 }
 
-#[allow(dead_code)]
-/// Create array from u64
-pub fn from_u64(mut pos: u64) -> Matrix {
-    let mut m = [[0u8; BOARD_SIZE]; BOARD_SIZE];
-    for row in m.iter_mut() {
-        for cell in row.iter_mut() {
+/// Create array from u64, inverse of to_u64 up to 4x4
+pub fn from_u64<const N: usize>(mut pos: u64) -> Matrix<N> {
+    let mut m = [[0u8; N]; N];
+    for row in m.iter_mut().rev() {
+        for cell in row.iter_mut().rev() {
             *cell = (pos & 0b1111) as u8;
             pos >>= 4;
         }
@@ -198,30 +226,8 @@ pub fn to_u16(m: [u8; BOARD_SIZE]) -> u16 {
     res
 }
 
-/// Create array from u16
-pub fn from_u16(m: &mut [u8; BOARD_SIZE], pos: u16) {
-    m[0] = ((pos & 0b1111_0000_0000_0000) >> 12) as u8;
-    m[1] = ((pos & 0b0000_1111_0000_0000) >> 8) as u8;
-    m[2] = ((pos & 0b0000_0000_1111_0000) >> 4) as u8;
-    m[3] = (pos & 0b0000_0000_0000_1111) as u8;
-}
-
-/// Convert to u16 id
-pub fn to_u16_rev(m: [u8; BOARD_SIZE]) -> u16 {
-    let res: u16 =
-        (m[0] as u16) + ((m[1] as u16) << 4) + ((m[2] as u16) << 8) + ((m[3] as u16) << 12);
-    res
-}
-
-/// Create array from u16
-pub fn from_u16_rev(m: &mut [u8; BOARD_SIZE], pos: u16) {
-    m[0] = (pos & 0b0000_0000_0000_1111) as u8;
-    m[1] = ((pos & 0b0000_0000_1111_0000) >> 4) as u8;
-    m[2] = ((pos & 0b0000_1111_0000_0000) >> 8) as u8;
-    m[3] = ((pos & 0b1111_0000_0000_0000) >> 12) as u8;
-}
-
 #[cfg(test)]
+#[allow(clippy::needless_range_loop)]
 mod tests {
     use super::*;
 
@@ -329,7 +335,7 @@ mod tests {
         let mut board = [[0u8; BOARD_SIZE]; BOARD_SIZE];
         for j in 0..BOARD_SIZE {
             for i in 0..BOARD_SIZE {
-                if j + i & 1 == 1 {
+                if (j + i) & 1 == 1 {
                     board[j][i] = 16;
                 }
             }
@@ -384,6 +390,15 @@ mod tests {
         assert_eq!(actual, 112316);
     }
 
+    #[test]
+    fn distinct_count_test() {
+        let mut board = [[0u8; BOARD_SIZE]; BOARD_SIZE];
+        assert_eq!(distinct_count(&board), 0);
+        board[0] = [1, 1, 2, 0];
+        board[3] = [15, 2, 0, 3];
+        assert_eq!(distinct_count(&board), 4);
+    }
+
     #[test]
     fn mirror_h_test() {
         let mut actual = [[0u8; BOARD_SIZE]; BOARD_SIZE];
@@ -397,4 +412,18 @@ mod tests {
         excepted[2][2] = 2;
         assert_eq!(actual, excepted);
     }
+
+    #[test]
+    fn mirror_v_test() {
+        let mut actual = [[0u8; BOARD_SIZE]; BOARD_SIZE];
+        actual[0][1] = 1;
+        actual[1][3] = 2;
+
+        mirror_v(&mut actual);
+
+        let mut excepted = [[0u8; BOARD_SIZE]; BOARD_SIZE];
+        excepted[0][2] = 1;
+        excepted[1][0] = 2;
+        assert_eq!(actual, excepted);
+    }
 }
@@ -17,21 +17,25 @@ You should have received a copy of the GNU General Public License
 along with game-2048-engine.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use super::board::BOARD_SIZE;
+use super::board::Board;
 use super::game::Game;
 use std::fmt;
 
-impl fmt::Display for Game {
+impl<const N: usize> fmt::Display for Game<N> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "score: {}", self.board.score)?;
+        write!(f, "{}", self.board)
+    }
+}
 
-        for j in 0..BOARD_SIZE {
-            for i in 0..BOARD_SIZE {
-                let value = if self.board.board[j][i] != 0 {
-                    1 << self.board.board[j][i]
-                } else {
-                    0
-                };
+/// Grid of tile values, readable by 'str::parse'
+impl<const N: usize> fmt::Display for Board<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.tiles() {
+            for (i, value) in row.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " ")?
+                }
                 write!(f, "{:>6}", value)?
             }
             writeln!(f)?
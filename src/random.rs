@@ -17,12 +17,182 @@ You should have received a copy of the GNU General Public License
 along with game-2048-engine.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use crate::board;
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Debug)]
-/// Linear congruential generator
-pub(super) struct Rnd {
+/// Chance of double value (4-tile) in percent
+pub(super) const DOUBLE_VALUE_PERCENT: u32 = 10;
+
+/// Source of random moves, own generators need only 'next_u64' and 'box_clone'
+pub trait RndMove: fmt::Debug + Send {
+    /// Next 64 random bits
+    fn next_u64(&mut self) -> u64;
+
+    /// Copy continuing the same sequence
+    fn box_clone(&self) -> Box<dyn RndMove>;
+
+    /// Uniform value in 0..bound, bound > 0
+    fn below(&mut self, bound: u32) -> u32 {
+        //Lemire, the biased low part is rejected
+        let threshold = bound.wrapping_neg() % bound;
+        loop {
+            let m = (self.next_u64() >> 32) * bound as u64;
+            if m as u32 >= threshold {
+                return (m >> 32) as u32;
+            }
+        }
+    }
+
+    /// Standard spawn, 10% double value, every empty cell is equally likely
+    /// returns: (value 1 or 2, position in empty cell)
+    fn next_move(&mut self, empty_count: u8) -> (u8, u8) {
+        let value = if self.below(100) < DOUBLE_VALUE_PERCENT {
+            2
+        } else {
+            1
+        };
+        (value, self.below(empty_count as u32) as u8)
+    }
+}
+
+/// Values of spawned tiles
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpawnValues {
+    /// 'next_move' of the generator, 4-tile with 10% chance
+    Standard,
+    /// (exponent, weight) pairs, 1 - 2-tile, 2 - 4-tile
+    Weighted(Vec<(u8, u32)>),
+}
+
+/// How many and which tiles are spawned
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpawnRules {
+    pub values: SpawnValues,
+    /// Random moves after every human move
+    pub tiles_per_turn: u8,
+    /// Tiles of the start position
+    pub starting_tiles: u8,
+}
+
+impl Default for SpawnRules {
+    /// Rules of the original game
+    fn default() -> Self {
+        SpawnRules {
+            values: SpawnValues::Standard,
+            tiles_per_turn: 1,
+            starting_tiles: 2,
+        }
+    }
+}
+
+/// Spawn rules which can't make a tile
+#[derive(Debug, PartialEq, Eq)]
+pub enum SpawnError {
+    /// No value has a positive weight
+    NoValues,
+    /// Exponent 0 is an empty cell
+    EmptyValue,
+    /// Sum of weights doesn't fit into u32
+    WeightOverflow,
+    /// Tile of the exponent can't be on the board
+    TooBig(u8),
+}
+
+impl fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpawnError::NoValues => write!(f, "no spawn value has a positive weight"),
+            SpawnError::EmptyValue => write!(f, "spawn value of exponent 0 is an empty cell"),
+            SpawnError::WeightOverflow => write!(f, "total weight of spawn values is too big"),
+            SpawnError::TooBig(exp) => write!(f, "spawn value of exponent {} is too big", exp),
+        }
+    }
+}
+
+impl Error for SpawnError {}
+
+impl SpawnValues {
+    /// Exponents which may be spawned
+    pub fn exponents(&self) -> Vec<u8> {
+        self.weights().iter().map(|&(exp, _)| exp).collect()
+    }
+
+    /// (exponent, weight) pairs of values which may be spawned
+    pub fn weights(&self) -> Vec<(u8, u32)> {
+        match self {
+            SpawnValues::Standard => {
+                vec![(1, 100 - DOUBLE_VALUE_PERCENT), (2, DOUBLE_VALUE_PERCENT)]
+            }
+            SpawnValues::Weighted(weights) => weights
+                .iter()
+                .filter(|&&(_, weight)| weight > 0)
+                .copied()
+                .collect(),
+        }
+    }
+}
+
+impl SpawnRules {
+    /// Every spawn must be a tile of the 'N' x 'N' board
+    pub fn validate<const N: usize>(&self) -> Result<(), SpawnError> {
+        let weights = match self.values {
+            SpawnValues::Standard => return Ok(()),
+            SpawnValues::Weighted(ref weights) => weights,
+        };
+        if weights.iter().any(|&(exp, _)| exp == 0) {
+            return Err(SpawnError::EmptyValue);
+        }
+        for &(exp, _) in weights {
+            let value = 1u64
+                .checked_shl(exp as u32)
+                .ok_or(SpawnError::TooBig(exp))?;
+            board::exponent::<N>(value).map_err(|_| SpawnError::TooBig(exp))?;
+        }
+        let total = weights
+            .iter()
+            .try_fold(0u32, |total, &(_, weight)| total.checked_add(weight))
+            .ok_or(SpawnError::WeightOverflow)?;
+        if total == 0 {
+            return Err(SpawnError::NoValues);
+        }
+        Ok(())
+    }
+
+    /// Rules must be valid
+    /// returns: (value, position in empty cell)
+    pub fn next_move(&self, rnd: &mut dyn RndMove, empty_count: u8) -> (u8, u8) {
+        match self.values {
+            SpawnValues::Standard => rnd.next_move(empty_count),
+            SpawnValues::Weighted(ref weights) => {
+                let total: u32 = weights.iter().map(|&(_, weight)| weight).sum();
+                let mut next = rnd.below(total);
+                let mut value = 0;
+                for &(exp, weight) in weights {
+                    if next < weight {
+                        value = exp;
+                        break;
+                    }
+                    next -= weight;
+                }
+                (value, rnd.below(empty_count as u32) as u8)
+            }
+        }
+    }
+}
+
+impl Clone for Box<dyn RndMove> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Linear congruential generator, only 6075 different sequences.
+/// Kept to reproduce games played before 'Xoshiro256'
+pub struct Lcg {
     pub(super) seed: u32,
 }
 
@@ -30,39 +200,34 @@ const MODULUS: u32 = 6075;
 const MULTIPLIER: u32 = 106;
 const INCREMENT: u32 = 1283;
 
-pub(super) trait RndMove {
-    /// returns: (value 1 or 2, position in empty cell)
-    fn next_move(&mut self, empty_count: u8) -> (u8, u8);
+#[derive(Debug, Clone)]
+/// xoshiro256** generator, default one
+pub struct Xoshiro256 {
+    state: [u64; 4],
 }
 
-static SEED: AtomicU32 = AtomicU32::new(0);
-static HAS_INIT: AtomicBool = AtomicBool::new(false);
+/// Games started without seed
+static GAME_COUNT: AtomicU64 = AtomicU64::new(0);
 
-/// create new 'Rnd' every game
-pub(super) fn get_rnd() -> Rnd {
-    if !HAS_INIT.compare_and_swap(false, true, Ordering::Relaxed) {
-        let rnd = Rnd::new();
-        SEED.store(rnd.seed, Ordering::Relaxed);
-        rnd
-    } else {
-        let mut rnd = Rnd::with_seed(SEED.load(Ordering::Relaxed));
-        SEED.store(rnd.next(), Ordering::Relaxed);
-        rnd
-    }
+/// create new generator every game, different within the same nanosecond too
+pub(super) fn get_rnd() -> Xoshiro256 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH);
+    let count = GAME_COUNT.fetch_add(1, Ordering::Relaxed);
+    Xoshiro256::with_seed(now.unwrap().as_nanos() as u64 ^ count.rotate_right(16))
 }
 
-impl Rnd {
-    pub(super) fn new() -> Self {
+impl Lcg {
+    pub fn new() -> Self {
         let start = SystemTime::now();
         let now = start.duration_since(UNIX_EPOCH);
-        Rnd {
+        Lcg {
             seed: now.unwrap().as_nanos() as u32 % MODULUS,
         }
     }
 
-    pub(super) fn with_seed(seed: u32) -> Self {
-        Rnd {
-            seed: seed as u32 % MODULUS,
+    pub fn with_seed(seed: u32) -> Self {
+        Lcg {
+            seed: seed % MODULUS,
         }
     }
 
@@ -73,16 +238,79 @@ impl Rnd {
     }
 }
 
-impl RndMove for Rnd {
+impl Default for Lcg {
+    fn default() -> Self {
+        Lcg::new()
+    }
+}
+
+impl RndMove for Lcg {
+    /// Value spread over the whole range, still 6075 different ones
+    fn next_u64(&mut self) -> u64 {
+        self.next() as u64 * (u64::MAX / MODULUS as u64)
+    }
+
+    fn box_clone(&self) -> Box<dyn RndMove> {
+        Box::new(self.clone())
+    }
+
+    /// The original mapping of games played with this generator
     fn next_move(&mut self, empty_count: u8) -> (u8, u8) {
         let next = self.next();
         // 10% double value
-        let value = if next > (MODULUS - 1) / 10 { 1 } else { 2 };
+        let value = if next > (MODULUS - 1) * DOUBLE_VALUE_PERCENT / 100 {
+            1
+        } else {
+            2
+        };
 
         (value, (next & (empty_count as u32 - 1)) as u8)
     }
 }
 
+/// Spread seed bits, recommended seeding of xoshiro
+fn split_mix(x: &mut u64) -> u64 {
+    *x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *x;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+impl Xoshiro256 {
+    /// Every seed is a different sequence
+    pub fn with_seed(seed: u64) -> Self {
+        let mut x = seed;
+        Xoshiro256 {
+            state: [
+                split_mix(&mut x),
+                split_mix(&mut x),
+                split_mix(&mut x),
+                split_mix(&mut x),
+            ],
+        }
+    }
+}
+
+impl RndMove for Xoshiro256 {
+    fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    fn box_clone(&self) -> Box<dyn RndMove> {
+        Box::new(self.clone())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -109,7 +337,7 @@ mod tests {
 
     #[test]
     fn next_move_test() {
-        let mut rnd = Rnd::new();
+        let mut rnd = Lcg::new();
         for c in 1..17 {
             for _ in 0..1000 {
                 let (value, pos) = rnd.next_move(c);
@@ -121,7 +349,7 @@ mod tests {
 
     #[test]
     fn next_move_freq_test() {
-        let mut rnd = Rnd::new();
+        let mut rnd = Lcg::new();
         let mut pos_freq = [0; 16];
         let mut value_freq = [0; 2];
         for _ in 0..100000 {
@@ -144,7 +372,7 @@ mod tests {
     fn spectral_test() {
         let mut file = File::create("spectral_test.csv").expect("Unable to create file");
         writeln!(file, "n,n+1").unwrap();
-        let mut rnd = Rnd::new();
+        let mut rnd = Lcg::new();
         for _ in 0..3000 {
             let seed = rnd.seed;
             let next = rnd.next();
@@ -152,11 +380,79 @@ mod tests {
         }
     }
 
+    #[test]
+    fn split_mix_test() {
+        let mut x = 0;
+        assert_eq!(split_mix(&mut x), 0xE220_A839_7B1D_CDAF);
+    }
+
+    #[test]
+    fn xoshiro_next_move_test() {
+        let mut rnd = Xoshiro256::with_seed(3);
+        let mut pos_freq = [0; 13];
+        let mut value_freq = [0; 2];
+        for _ in 0..130000 {
+            let (value, pos) = rnd.next_move(13);
+            pos_freq[pos as usize] += 1;
+            value_freq[value as usize - 1] += 1;
+        }
+        for freq in pos_freq {
+            assert!((9000..11000).contains(&freq));
+        }
+        assert!((12000..14000).contains(&value_freq[1]));
+    }
+
+    #[test]
+    fn xoshiro_seed_test() {
+        let mut first: Vec<u64> = (0..10000)
+            .map(|seed| Xoshiro256::with_seed(seed).next_u64())
+            .collect();
+        first.sort_unstable();
+        first.dedup();
+        assert_eq!(first.len(), 10000);
+
+        let mut rnd: Box<dyn RndMove> = Box::new(Xoshiro256::with_seed(5));
+        rnd.next_u64();
+        let mut copy = rnd.clone();
+        assert_eq!(copy.next_u64(), rnd.next_u64());
+    }
+
+    #[test]
+    fn below_test() {
+        let mut rnd = Xoshiro256::with_seed(3);
+        let mut freq = [0; 3];
+        for _ in 0..30000 {
+            freq[rnd.below(3) as usize] += 1;
+        }
+        for count in freq {
+            assert!((9500..10500).contains(&count));
+        }
+        assert_eq!(rnd.below(1), 0);
+        assert!(rnd.below(u32::MAX) < u32::MAX);
+    }
+
+    #[test]
+    fn weighted_values_test() {
+        let rules = SpawnRules {
+            values: SpawnValues::Weighted(vec![(1, 1), (2, 0), (3, 3)]),
+            ..SpawnRules::default()
+        };
+        let mut rnd = Xoshiro256::with_seed(3);
+        let mut freq = [0; 4];
+        for _ in 0..40000 {
+            let (value, pos) = rules.next_move(&mut rnd, 5);
+            assert!(pos < 5);
+            freq[value as usize] += 1;
+        }
+        assert_eq!(freq[0] + freq[2], 0);
+        assert!((9000..11000).contains(&freq[1]));
+    }
+
     #[test]
     fn minimal_cycle() {
         let mut min_cycle = MODULUS * 2;
         for i in 0..MODULUS {
-            let mut rnd = Rnd::with_seed(i);
+            let mut rnd = Lcg::with_seed(i);
             rnd.next();
             let mut c = 1;
             while rnd.seed != i && c < MODULUS * 2 {
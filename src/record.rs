@@ -0,0 +1,452 @@
+/* record.rs -- recorded games and their replay.
+Copyright (C) 2021 fuggy
+
+This file is part of game-2048-engine.
+
+game-2048-engine is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+game-2048-engine is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with game-2048-engine.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::board::{self, Board, BoardError};
+use crate::direction::Direction;
+use crate::engine::moves::Move;
+use crate::game::Game;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The first line of a record file
+const HEADER: &str = "game-2048-record 1";
+
+/// Move of the game and score after it
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Step {
+    pub turn: Move,
+    pub score: u32,
+}
+
+/// Game from the start position to the end position
+#[derive(Debug, Clone)]
+pub struct Record<const N: usize> {
+    /// seed of the generator, if the game was started by seed
+    pub seed: Option<u64>,
+    pub start: Board<N>,
+    pub steps: Vec<Step>,
+    pub end: Board<N>,
+}
+
+/// Wrong record file or replay different from the record
+#[derive(Debug)]
+pub enum RecordError {
+    Io(io::Error),
+    /// Line of the file can't be read
+    Syntax {
+        line: usize,
+        text: String,
+    },
+    Board(BoardError),
+    /// Move isn't possible or its result is different, 0 - the start position
+    Mismatch {
+        step: usize,
+    },
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordError::Io(err) => write!(f, "record file error: {}", err),
+            RecordError::Syntax { line, text } => write!(f, "line {}: '{}'", line, text),
+            RecordError::Board(err) => write!(f, "record board: {}", err),
+            RecordError::Mismatch { step } => write!(f, "replay differs at step {}", step),
+        }
+    }
+}
+
+impl Error for RecordError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RecordError::Io(err) => Some(err),
+            RecordError::Board(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RecordError {
+    fn from(err: io::Error) -> Self {
+        RecordError::Io(err)
+    }
+}
+
+impl From<BoardError> for RecordError {
+    fn from(err: BoardError) -> Self {
+        RecordError::Board(err)
+    }
+}
+
+impl<const N: usize> Record<N> {
+    /// Write as text file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), RecordError> {
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    /// Read text file written by 'save'
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, RecordError> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Play the game again step by step
+    pub fn replay(&self) -> Replay<'_, N> {
+        let mut game = Game::with_board(self.start, self.seed.unwrap_or(0));
+        game.keep_playing();
+        Replay {
+            record: self,
+            game,
+            step: 0,
+        }
+    }
+}
+
+/// Header, seed, start score and board, steps, end score and board
+/// human step: direction key and score, random step: tile value and position in empty cells
+impl<const N: usize> fmt::Display for Record<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        match self.seed {
+            Some(seed) => writeln!(f, "seed {}", seed)?,
+            None => writeln!(f, "seed -")?,
+        }
+        writeln!(f, "start {}", self.start.score)?;
+        write!(f, "{}", self.start)?;
+        writeln!(f, "moves {}", self.steps.len())?;
+        for step in &self.steps {
+            match step.turn {
+                Move::Human(dir) => writeln!(f, "{} {}", char::from(dir), step.score)?,
+                Move::Random(exp, pos) => writeln!(f, "{} {}", 1u64 << exp, pos)?,
+            }
+        }
+        writeln!(f, "end {}", self.end.score)?;
+        write!(f, "{}", self.end)
+    }
+}
+
+/// Lines with their numbers, empty lines are skipped
+struct Lines<'a> {
+    lines: std::iter::Enumerate<std::str::Lines<'a>>,
+    /// number of the last read line
+    number: usize,
+}
+
+impl<'a> Lines<'a> {
+    fn new(s: &'a str) -> Self {
+        Lines {
+            lines: s.lines().enumerate(),
+            number: 0,
+        }
+    }
+
+    fn next(&mut self) -> Result<(usize, &'a str), RecordError> {
+        for (index, line) in &mut self.lines {
+            self.number = index + 1;
+            let line = line.trim();
+            if !line.is_empty() {
+                return Ok((self.number, line));
+            }
+        }
+        Err(RecordError::Syntax {
+            line: self.number,
+            text: "unexpected end".to_string(),
+        })
+    }
+
+    /// Line starting with the word, returns the rest
+    fn expect(&mut self, word: &str) -> Result<(usize, &'a str), RecordError> {
+        let (number, line) = self.next()?;
+        match line.strip_prefix(word) {
+            Some(rest) => Ok((number, rest.trim())),
+            None => Err(syntax(number, line)),
+        }
+    }
+
+    fn board<const N: usize>(&mut self) -> Result<Board<N>, RecordError> {
+        let mut rows = Vec::with_capacity(N);
+        for _ in 0..N {
+            rows.push(self.next()?.1);
+        }
+        Ok(rows.join("\n").parse()?)
+    }
+}
+
+fn syntax(line: usize, text: &str) -> RecordError {
+    RecordError::Syntax {
+        line,
+        text: text.to_string(),
+    }
+}
+
+fn parse_step<const N: usize>(line: usize, text: &str, score: u32) -> Result<Step, RecordError> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let err = || syntax(line, text);
+    if words.len() != 2 {
+        return Err(err());
+    }
+
+    let mut chars = words[0].chars();
+    let dir = match (chars.next(), chars.next()) {
+        (Some(ch), None) => Direction::try_from(ch).ok(),
+        _ => None,
+    };
+    let step = match dir {
+        Some(dir) => Step {
+            turn: Move::Human(dir),
+            score: words[1].parse().map_err(|_| err())?,
+        },
+        None => Step {
+            turn: Move::Random(
+                words[0]
+                    .parse()
+                    .ok()
+                    .and_then(|value| board::exponent::<N>(value).ok())
+                    .filter(|&exp| exp > 0)
+                    .ok_or_else(err)?,
+                words[1].parse().map_err(|_| err())?,
+            ),
+            score,
+        },
+    };
+    Ok(step)
+}
+
+impl<const N: usize> FromStr for Record<N> {
+    type Err = RecordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = Lines::new(s);
+        let (number, header) = lines.next()?;
+        if header != HEADER {
+            return Err(syntax(number, header));
+        }
+
+        let (number, seed) = lines.expect("seed")?;
+        let seed = match seed {
+            "-" => None,
+            _ => Some(seed.parse().map_err(|_| syntax(number, seed))?),
+        };
+
+        let (number, score) = lines.expect("start")?;
+        let mut start: Board<N> = lines.board()?;
+        start.score = score.parse().map_err(|_| syntax(number, score))?;
+        let (number, count) = lines.expect("moves")?;
+        let count: usize = count.parse().map_err(|_| syntax(number, count))?;
+
+        let mut steps = Vec::with_capacity(count);
+        let mut score = start.score;
+        for _ in 0..count {
+            let (number, text) = lines.next()?;
+            let step = parse_step::<N>(number, text, score)?;
+            score = step.score;
+            steps.push(step);
+        }
+
+        let (number, score) = lines.expect("end")?;
+        let mut end: Board<N> = lines.board()?;
+        end.score = score.parse().map_err(|_| syntax(number, score))?;
+
+        Ok(Record {
+            seed,
+            start,
+            steps,
+            end,
+        })
+    }
+}
+
+/// Recorded game played again
+pub struct Replay<'a, const N: usize> {
+    record: &'a Record<N>,
+    game: Game<N>,
+    step: usize,
+}
+
+impl<'a, const N: usize> Replay<'a, N> {
+    /// Make the next recorded move, the last step is compared with the end board
+    /// returns: None after the last step
+    pub fn step(&mut self) -> Option<Result<Move, RecordError>> {
+        let step = *self.record.steps.get(self.step)?;
+        self.step += 1;
+        let mismatch = RecordError::Mismatch { step: self.step };
+
+        let made = match step.turn {
+            Move::Human(dir) => self.game.human_move(dir),
+            Move::Random(value, pos) => self.game.set_random_move((value, pos)),
+        };
+        if !made || self.game.board.score != step.score {
+            return Some(Err(mismatch));
+        }
+        if self.step == self.record.steps.len() && !self.is_end() {
+            return Some(Err(mismatch));
+        }
+        Some(Ok(step.turn))
+    }
+
+    /// Position after the replayed steps
+    pub fn game(&self) -> &Game<N> {
+        &self.game
+    }
+
+    fn is_end(&self) -> bool {
+        let end = &self.record.end;
+        self.game.board.tiles() == end.tiles() && self.game.board.score == end.score
+    }
+
+    /// Replay all remaining steps
+    /// returns: the end position
+    pub fn verify(mut self) -> Result<Game<N>, RecordError> {
+        if self.record.steps.is_empty() && !self.is_end() {
+            return Err(RecordError::Mismatch { step: 0 });
+        }
+        while let Some(result) = self.step() {
+            result?;
+        }
+        Ok(self.game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board;
+
+    fn played_game() -> Game {
+        board::load_cache();
+        let mut game: Game = Game::with_seed(3);
+        for _ in 0..20 {
+            for dir in [Direction::Left, Direction::Down, Direction::Right] {
+                game.make_move(dir);
+            }
+        }
+        game
+    }
+
+    #[test]
+    fn should_replay_record() {
+        let game = played_game();
+        let record = game.record().unwrap();
+        assert_eq!(record.seed, Some(3));
+        assert_eq!(record.steps.len(), game.board.move_count as usize);
+
+        let replayed = record.replay().verify().unwrap();
+        assert_eq!(replayed.board.tiles(), game.board.tiles());
+        assert_eq!(replayed.board.score, game.board.score);
+    }
+
+    #[test]
+    fn should_forget_undone_steps() {
+        let mut game = played_game();
+        let count = game.record().unwrap().steps.len();
+        assert!(game.undo());
+        assert_eq!(game.record().unwrap().steps.len(), count - 1);
+        assert!(game.redo());
+        let record = game.record().unwrap();
+        assert_eq!(record.steps.len(), count);
+        record.replay().verify().unwrap();
+    }
+
+    #[test]
+    fn should_parse_written_record() {
+        let record = played_game().record().unwrap();
+        let text = record.to_string();
+        let parsed: Record<4> = text.parse().unwrap();
+
+        assert_eq!(parsed.seed, record.seed);
+        assert_eq!(parsed.steps, record.steps);
+        assert_eq!(parsed.start.tiles(), record.start.tiles());
+        assert_eq!(parsed.end.tiles(), record.end.tiles());
+        assert_eq!(parsed.to_string(), text);
+        parsed.replay().verify().unwrap();
+    }
+
+    #[test]
+    fn should_save_and_load() {
+        let record = played_game().record().unwrap();
+        let path = std::env::temp_dir().join("game-2048-record-test.txt");
+        record.save(&path).unwrap();
+        let loaded = Record::<4>::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap().steps, record.steps);
+    }
+
+    #[test]
+    fn should_find_mismatch() {
+        let mut record = played_game().record().unwrap();
+        record.steps[4].score += 4;
+        assert!(matches!(
+            record.replay().verify(),
+            Err(RecordError::Mismatch { step: 5 })
+        ));
+
+        let mut record = played_game().record().unwrap();
+        record.end.score += 4;
+        let last = record.steps.len();
+        let mut replay = record.replay();
+        for _ in 1..last {
+            assert!(replay.step().unwrap().is_ok());
+        }
+        assert!(matches!(
+            replay.step(),
+            Some(Err(RecordError::Mismatch { step })) if step == last
+        ));
+        assert!(replay.step().is_none());
+    }
+
+    #[test]
+    fn should_write_spawns_as_tiles() {
+        let mut game = played_game();
+        assert!(game.set_random_move((2, 0)));
+        let text = game.record().unwrap().to_string();
+        let moves: Vec<&str> = text
+            .lines()
+            .skip_while(|line| !line.starts_with("moves"))
+            .collect();
+        //end score and board follow the last step
+        assert_eq!(moves[moves.len() - 6], "4 0");
+
+        for wrong in ["1 0", "3 0", "x 0"] {
+            let wrong_text = text.replacen("\n4 0\n", &format!("\n{}\n", wrong), 1);
+            assert!(matches!(
+                wrong_text.parse::<Record<4>>(),
+                Err(RecordError::Syntax { text, .. }) if text == wrong
+            ));
+        }
+    }
+
+    #[test]
+    fn should_reject_wrong_text() {
+        let text = played_game().record().unwrap().to_string();
+        let wrong = text.replacen("seed 3", "seed x", 1);
+        assert!(matches!(
+            wrong.parse::<Record<4>>(),
+            Err(RecordError::Syntax { line: 2, .. })
+        ));
+        let short: String = text.lines().take(10).collect::<Vec<_>>().join("\n");
+        assert!(matches!(
+            short.parse::<Record<4>>(),
+            Err(RecordError::Syntax { line: 10, .. })
+        ));
+    }
+}
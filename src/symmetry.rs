@@ -0,0 +1,187 @@
+/* symmetry.rs -- eight symmetries of the square board.
+Copyright (C) 2021 fuggy
+
+This file is part of game-2048-engine.
+
+game-2048-engine is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+game-2048-engine is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with game-2048-engine.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::direction::Direction;
+use crate::matrix;
+
+/// Rotations and reflections of the board,
+/// a move of the board is the mapped move of the transformed board
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum Symmetry {
+    Identity,
+    /// Clockwise
+    Rotate90,
+    Rotate180,
+    /// Counterclockwise
+    Rotate270,
+    /// Rows in reverse order, top and bottom are swapped
+    MirrorH,
+    /// Cells of rows in reverse order, left and right are swapped
+    MirrorV,
+    /// Rows become columns
+    Transpose,
+    /// Reflection over the other diagonal
+    AntiTranspose,
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 8] = [
+        Symmetry::Identity,
+        Symmetry::Rotate90,
+        Symmetry::Rotate180,
+        Symmetry::Rotate270,
+        Symmetry::MirrorH,
+        Symmetry::MirrorV,
+        Symmetry::Transpose,
+        Symmetry::AntiTranspose,
+    ];
+
+    /// Steps applied in order: transpose, mirror vertically, mirror horizontally
+    pub(crate) fn steps(self) -> (bool, bool, bool) {
+        match self {
+            Symmetry::Identity => (false, false, false),
+            Symmetry::Rotate90 => (true, true, false),
+            Symmetry::Rotate180 => (false, true, true),
+            Symmetry::Rotate270 => (true, false, true),
+            Symmetry::MirrorH => (false, false, true),
+            Symmetry::MirrorV => (false, true, false),
+            Symmetry::Transpose => (true, false, false),
+            Symmetry::AntiTranspose => (true, true, true),
+        }
+    }
+
+    /// Symmetry returning the transformed board back
+    pub fn inverse(self) -> Symmetry {
+        match self {
+            Symmetry::Rotate90 => Symmetry::Rotate270,
+            Symmetry::Rotate270 => Symmetry::Rotate90,
+            //reflections and half turn are involutions
+            other => other,
+        }
+    }
+
+    /// Direction on the transformed board doing the same move
+    pub fn map_direction(self, dir: Direction) -> Direction {
+        let (transposed, mirrored_v, mirrored_h) = self.steps();
+        let mut dir = dir;
+        if transposed {
+            dir = match dir {
+                Direction::Left => Direction::Up,
+                Direction::Up => Direction::Left,
+                Direction::Right => Direction::Down,
+                Direction::Down => Direction::Right,
+            };
+        }
+        if mirrored_v {
+            dir = match dir {
+                Direction::Left => Direction::Right,
+                Direction::Right => Direction::Left,
+                other => other,
+            };
+        }
+        if mirrored_h {
+            dir = match dir {
+                Direction::Up => Direction::Down,
+                Direction::Down => Direction::Up,
+                other => other,
+            };
+        }
+        dir
+    }
+
+    /// Transformed copy of the matrix
+    pub(crate) fn apply<const N: usize>(self, m: &[[u8; N]; N]) -> [[u8; N]; N] {
+        let (transposed, mirrored_v, mirrored_h) = self.steps();
+        let mut m = *m;
+        if transposed {
+            matrix::transpose(&mut m);
+        }
+        if mirrored_v {
+            matrix::mirror_v(&mut m);
+        }
+        if mirrored_h {
+            matrix::mirror_h(&mut m);
+        }
+        m
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rustfmt::skip]
+    const MATRIX: [[u8; 3]; 3] =
+       [[1, 2, 3],
+        [4, 5, 6],
+        [7, 8, 9]];
+
+    #[test]
+    #[rustfmt::skip]
+    fn should_rotate_and_reflect() {
+        assert_eq!(Symmetry::Rotate90.apply(&MATRIX),
+           [[7, 4, 1],
+            [8, 5, 2],
+            [9, 6, 3]]);
+        assert_eq!(Symmetry::Rotate270.apply(&MATRIX),
+           [[3, 6, 9],
+            [2, 5, 8],
+            [1, 4, 7]]);
+        assert_eq!(Symmetry::AntiTranspose.apply(&MATRIX),
+           [[9, 6, 3],
+            [8, 5, 2],
+            [7, 4, 1]]);
+        assert_eq!(Symmetry::MirrorV.apply(&MATRIX),
+           [[3, 2, 1],
+            [6, 5, 4],
+            [9, 8, 7]]);
+    }
+
+    #[test]
+    fn should_inverse() {
+        for sym in Symmetry::ALL {
+            assert_eq!(sym.inverse().apply(&sym.apply(&MATRIX)), MATRIX);
+            assert_eq!(sym.inverse().inverse(), sym);
+        }
+    }
+
+    #[test]
+    fn should_map_direction_back() {
+        let dirs = [
+            Direction::Left,
+            Direction::Right,
+            Direction::Up,
+            Direction::Down,
+        ];
+        for sym in Symmetry::ALL {
+            for dir in dirs {
+                let mapped = sym.map_direction(dir);
+                assert_eq!(sym.inverse().map_direction(mapped), dir);
+            }
+        }
+        assert_eq!(
+            Symmetry::Rotate90.map_direction(Direction::Up),
+            Direction::Right
+        );
+        assert_eq!(
+            Symmetry::Rotate90.map_direction(Direction::Left),
+            Direction::Up
+        );
+    }
+}